@@ -0,0 +1,33 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    queue_edit::{dedupe_key, dedupe_queue},
+    utils::{author_call, send_reply}
+};
+use std::collections::HashMap;
+use super::queue::{extract_track_data, resolve_lazy_metadata};
+
+/// Remove tracks that are already in the queue
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn dedupe(ctx: Context<'_>) -> Result<(), Error> {
+    if let Some(handler) = author_call(&ctx).await {
+        ctx.defer_ephemeral().await?;
+        let tracks = handler.lock().await.queue().current_queue();
+        // Lazy tracks only know what they link to once their metadata is generated
+        resolve_lazy_metadata(tracks.clone()).await;
+
+        let mut keys = HashMap::new();
+        for track in tracks {
+            if let Some(key) = dedupe_key(&extract_track_data(track.clone(), false).await.0.source_url) {
+                keys.insert(track.uuid(), key);
+            }
+        }
+
+        let duplicates = handler.lock().await.queue().modify_queue(|queue| dedupe_queue(queue, |track| keys.get(&track.uuid()).cloned()));
+        for track in duplicates.iter() {
+            let _ = track.stop();
+        }
+        send_reply(&ctx, &format!("Removed {} Duplicates", duplicates.len())).await;
+    }
+    Ok(())
+}
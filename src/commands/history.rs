@@ -0,0 +1,94 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    history::HistoryEntry,
+    utils::send_error
+};
+use poise::serenity_prelude::MessageComponentInteraction;
+use serenity::{builder::CreateEmbed, utils::Color};
+use std::{ sync::Arc, time::{ Duration, UNIX_EPOCH } };
+use futures::stream::*;
+use super::queue::{create_buttons, format_track};
+
+const ENTRIES_PER_PAGE: usize = 10;
+
+/// The tracks that finished playing, newest first
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn history(ctx: Context<'_>, page: Option<u32>) -> Result<(), Error> {
+    if let Some(guild_id) = ctx.guild_id() {
+        let entries = ctx.data().history.entries(guild_id.0).await;
+        if entries.is_empty() {
+            send_error(&ctx, "Nothing Has Finished Playing Yet").await;
+            return Ok(());
+        }
+        let page_count = entries.len().div_ceil(ENTRIES_PER_PAGE) as u32;
+        let mut page = page.unwrap_or(0).min(page_count - 1);
+        let history_embed = create_history_embed(&entries, page, page_count);
+        let reply_handle = ctx.send(
+            |msg| msg
+            .allowed_mentions(|s| s.replied_user(true))
+            .embed(|embed| {embed.clone_from(&history_embed); embed})
+            .components(|components| components.set_action_row(create_buttons(page, page_count)))
+        ).await?;
+
+        let mut collector = reply_handle.message().await?.await_component_interactions(ctx).timeout(Duration::from_secs(30)).author_id(ctx.author().id).build();
+
+        while let Some(message_collector) = collector.next().await {
+            match message_collector.data.custom_id.as_str() {
+                "prev" => {
+                    page = page.saturating_sub(1);
+                    update_history_embed(page, message_collector, ctx).await;
+                },
+                "next" => {
+                    page += 1;
+                    update_history_embed(page, message_collector, ctx).await;
+                },
+                "reload" => {
+                    update_history_embed(page, message_collector, ctx).await;
+                }
+                _ => ()
+            }
+        }
+
+        ctx.data().delete_after_delay(reply_handle, Duration::ZERO).await;
+    }
+    Ok(())
+}
+
+pub fn create_history_embed(entries: &[HistoryEntry], page: u32, page_count: u32) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title("History").footer(|footer| footer.text(format!("Page: {}/{}  tracks: {}", page + 1, page_count.max(1), entries.len())));
+    let mut played = String::new();
+    for (i, entry) in entries.iter().enumerate().skip(page as usize * ENTRIES_PER_PAGE).take(ENTRIES_PER_PAGE) {
+        played += format!("{}. {}\n", i + 1, format_entry(entry)).as_str();
+    }
+    if played.is_empty() {
+        played = "*Nothing*".to_string();
+    }
+    embed.description(played);
+    embed.color(Color::PURPLE);
+    embed
+}
+
+fn format_entry(entry: &HistoryEntry) -> String {
+    let mut formatted_entry = format_track(entry.metadata.clone(), None);
+    if let Some(ref added_by) = entry.added_by {
+        formatted_entry += format!(" | {}", added_by.name).as_str();
+    }
+    // Discord shows these as "5 minutes ago" in the reader's own locale
+    if let Ok(played_at) = entry.played_at.duration_since(UNIX_EPOCH) {
+        formatted_entry += format!(" | <t:{}:R>", played_at.as_secs()).as_str();
+    }
+    formatted_entry
+}
+
+pub async fn update_history_embed(page: u32, message_collector: Arc<MessageComponentInteraction>, ctx: Context<'_>) {
+    let channel_id = message_collector.message.channel_id.0;
+    let message_id = message_collector.message.id.0;
+    if let (Some(guild_id), Ok(mut message)) = (ctx.guild_id(), ctx.serenity_context().http.get_message(channel_id, message_id).await) {
+        let entries = ctx.data().history.entries(guild_id.0).await;
+        let page_count = entries.len().div_ceil(ENTRIES_PER_PAGE) as u32;
+        let _ = message.edit(ctx, |f| f.set_embed(create_history_embed(&entries, page, page_count)).components(|components| components.set_action_row(create_buttons(page, page_count)))).await;
+        let _ = message_collector.defer(ctx).await;
+    }
+}
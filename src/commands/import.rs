@@ -0,0 +1,122 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    utils::{send_error, describe_query_error, join_call},
+    convert_query::{lazier_input, QueryOptions, QueuedInput},
+    playlist_import::{parse_entries, truncate_report, ImportEntry}
+};
+use poise::serenity_prelude::Attachment;
+use serenity::utils::Color;
+use super::play::enqueue_inputs;
+
+const MAX_IMPORT_LINES: usize = 500;
+/// Bytes, far more than `MAX_IMPORT_LINES` lines ever take up
+const MAX_IMPORT_SIZE: u64 = 1_000_000;
+
+enum ImportOutcome {
+    Resolved(usize),
+    Searched,
+    Fallback(String),
+    Failed(String)
+}
+
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "An .m3u, .pls or text file with one link or query per line"] file: Attachment
+) -> Result<(), Error> {
+    if let Some(guild) = ctx.guild() {
+        if let Some(user_voice_state) = guild.voice_states.get(&ctx.author().id) {
+            if file.size > MAX_IMPORT_SIZE {
+                send_error(&ctx, "The File Is Too Big").await;
+                return Ok(());
+            }
+            ctx.defer().await?;
+            let content = match file.download().await {
+                Ok(content) => String::from_utf8_lossy(&content).to_string(),
+                Err(_) => {
+                    send_error(&ctx, "Couldn't Download The File").await;
+                    return Ok(());
+                }
+            };
+            let entries = parse_entries(&content, &file.filename);
+            if entries.is_empty() {
+                send_error(&ctx, "The File Has No Entries").await;
+                return Ok(());
+            }
+
+            let mut inputs: Vec<QueuedInput> = vec![];
+            let mut report: Vec<String> = vec![];
+            for (index, entry) in entries.iter().take(MAX_IMPORT_LINES).enumerate() {
+                let outcome = resolve_entry(&ctx, entry, &mut inputs).await;
+                report.push(format_outcome(index + 1, &entry.query, outcome));
+            }
+            if entries.len() > MAX_IMPORT_LINES {
+                report.push(format!("*Skipped {} lines past the limit of {}*", entries.len() - MAX_IMPORT_LINES, MAX_IMPORT_LINES));
+            }
+
+            let handler = join_call(&ctx, guild.id, user_voice_state.channel_id.unwrap()).await;
+            let mut handler_guard = handler.lock().await;
+
+            if user_voice_state.channel_id.map(|f| f.0) != handler_guard.current_channel().map(|f| f.0) {
+                send_error(&ctx, "You're In a Different Channel").await;
+                return Ok(());
+            }
+
+            let (handles, _) = enqueue_inputs(&ctx, &mut handler_guard, inputs, None).await;
+            drop(handler_guard);
+
+            ctx.send(
+                |msg| msg
+                    .reply(true)
+                    .allowed_mentions(|s| s.replied_user(true))
+                    .embed(|embed| embed
+                        .title(format!("Imported {} tracks from {}", handles.len(), file.filename))
+                        .description(truncate_report(report))
+                        .color(Color::PURPLE))
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn resolve_entry(ctx: &Context<'_>, entry: &ImportEntry, inputs: &mut Vec<QueuedInput>) -> ImportOutcome {
+    if ctx.data().sources.is_search(&entry.query) {
+        return match lazier_input(entry.query.clone()).await {
+            Ok(input) => {
+                inputs.push(input);
+                ImportOutcome::Searched
+            },
+            Err(err) => ImportOutcome::Failed(describe_query_error(&err))
+        };
+    }
+
+    let options = QueryOptions { user_id: Some(ctx.author().id.0), ..Default::default() };
+    let failure = match ctx.data().convert_query(&entry.query, options).await {
+        Ok(resolved_inputs) => {
+            let count = resolved_inputs.len();
+            inputs.extend(resolved_inputs);
+            return ImportOutcome::Resolved(count);
+        },
+        Err(err) => describe_query_error(&err)
+    };
+
+    let search_query = entry.title.clone().unwrap_or(entry.query.clone());
+    match lazier_input(search_query).await {
+        Ok(input) => {
+            inputs.push(input);
+            ImportOutcome::Fallback(failure)
+        },
+        Err(err) => ImportOutcome::Failed(describe_query_error(&err))
+    }
+}
+
+fn format_outcome(line_number: usize, query: &str, outcome: ImportOutcome) -> String {
+    let query: String = query.chars().take(60).collect();
+    match outcome {
+        ImportOutcome::Resolved(count) => format!("{}. ✅ `{}` | {} tracks", line_number, query, count),
+        ImportOutcome::Searched => format!("{}. 🔎 `{}`", line_number, query),
+        ImportOutcome::Fallback(reason) => format!("{}. ⚠️ `{}` | {}, queued as a search", line_number, query, reason),
+        ImportOutcome::Failed(reason) => format!("{}. ❌ `{}` | {}", line_number, query, reason)
+    }
+}
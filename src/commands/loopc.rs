@@ -1,12 +1,15 @@
 use diwa_rs::{
     Context,
     error::Error,
+    loop_mode::LoopMode,
     utils::{send_error, send_reply}
 };
-use songbird::tracks::LoopState;
 
 #[poise::command(slash_command, prefix_command, rename = "loop")]
-pub async fn loopc(ctx: Context<'_>) -> Result<(), Error> {
+pub async fn loopc(
+    ctx: Context<'_>,
+    #[description = "Leave it out to switch to the next mode"] mode: Option<LoopMode>
+) -> Result<(), Error> {
     if let Some(guild) = ctx.guild() {
         if let Some(user_voice_state) = guild.voice_states.get(&ctx.author().id) {
             let manager = songbird::get(&ctx.serenity_context()).await.unwrap();
@@ -17,28 +20,28 @@ pub async fn loopc(ctx: Context<'_>) -> Result<(), Error> {
                     send_error(&ctx, "You're In a Different Channel").await;
                     return Ok(());
                 }
+                let loop_modes = &ctx.data().loop_modes;
+                let mode = match mode {
+                    Some(mode) => mode,
+                    None => loop_modes.get(guild.id.0).await.next()
+                };
+                loop_modes.set(guild.id.0, mode).await;
+
+                // Tracks starting later are looped by the loop event handler, the current one has already started
                 if let Some(current_track) = handler_guard.queue().current() {
                     drop(handler_guard);
-                    if let Ok(info) = current_track.get_info().await {
-                        match info.loops {
-                            LoopState::Finite(_) => {
-                                if let Err(_) = current_track.enable_loop() {
-                                    send_error(&ctx, "Couldn't Enable Looping").await;
-                                }
-                                send_reply(&ctx, "Looping Enabled").await;
-                            },
-                            LoopState::Infinite => {
-                                if let Err(_) = current_track.disable_loop() {
-                                    send_error(&ctx, "Couldn't Disable Looping").await;
-                                }
-                                send_reply(&ctx, "Looping Disabled").await;
-                            }
-                        }
+                    let result = match mode {
+                        LoopMode::Track => current_track.enable_loop(),
+                        LoopMode::Off | LoopMode::Queue => current_track.disable_loop()
+                    };
+                    if let Err(_) = result {
+                        send_error(&ctx, "Couldn't Change Looping of the Current Track").await;
+                        return Ok(());
                     }
                 }
-                
+                send_reply(&ctx, &format!("Loop Mode: {}", mode)).await;
             }
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
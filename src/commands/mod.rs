@@ -6,4 +6,18 @@ pub mod queue;
 pub mod pause;
 pub mod resume;
 pub mod loopc;
-pub mod stop;
\ No newline at end of file
+pub mod stop;
+pub mod import;
+pub mod spotify;
+pub mod quota;
+pub mod search;
+pub mod remove;
+pub mod move_track;
+pub mod swap;
+pub mod skipto;
+pub mod shuffle;
+pub mod dedupe;
+pub mod sort;
+pub mod playnext;
+pub mod history;
+pub mod previous;
\ No newline at end of file
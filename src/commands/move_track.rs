@@ -0,0 +1,27 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    queue_edit,
+    utils::{author_call, send_error, send_reply, track_title}
+};
+
+/// Move a queued track to another position
+#[poise::command(slash_command, prefix_command, guild_only, rename = "move")]
+pub async fn move_track(
+    ctx: Context<'_>,
+    #[description = "The track number from /queue"] from: usize,
+    #[description = "Where it should end up"] to: usize
+) -> Result<(), Error> {
+    if let Some(handler) = author_call(&ctx).await {
+        let handler_guard = handler.lock().await;
+        let moved = handler_guard.queue().modify_queue(|queue| queue_edit::move_track(queue, from, to));
+        let track = handler_guard.queue().current_queue().get(to).cloned();
+        drop(handler_guard);
+        match (moved, track) {
+            (Ok(_), Some(track)) => send_reply(&ctx, &format!("Moved {} To {}", track_title(&track).await, to)).await,
+            (Err(err), _) => send_error(&ctx, &err.user_description()).await,
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -1,105 +1,279 @@
 use diwa_rs::{
+    query_error,
     Context,
     error::Error,
     LazyMetadataTrait,
-    MetadataEventHandler,
     AddedBy,
+    TrackDataTrait,
+    TrackSourceTrait,
     utils::create_now_playing_embed, MiniMetadata,
-    utils::format_duration,
-    utils::send_error
+    utils::{format_duration, format_metadata_duration, format_title},
+    http_stream::follow_stream_title,
+    utils::{send_error, describe_query_error, format_result_duration, truncate, join_call},
+    convert_query::{PlaylistMode, ArtistMode, QueryOptions, QueuedInput},
+    queue_edit::insert_enqueued
 };
-use poise::serenity_prelude::CreateEmbed;
+use poise::{ ReplyHandle, AutocompleteChoice, serenity_prelude::{CreateEmbed, Attachment} };
 use songbird::{
-    tracks::{create_player, TrackHandle},
-    events::{Event, TrackEvent}
+    Call,
+    tracks::{create_player, TrackHandle}
 };
 use serenity::utils::Color;
+use tokio::{ spawn, sync::watch };
+use std::{ sync::Arc, time::{ Duration, Instant } };
+
+const PROGRESS_UPDATE_INTERVAL: Duration = Duration::from_secs(2);
+/// Discord's limits for autocomplete
+const MAX_CHOICES: usize = 25;
+const MAX_CHOICE_LENGTH: usize = 100;
+/// Shorter text matches too much to be worth a search
+const MIN_SEARCH_LENGTH: usize = 3;
 
 #[poise::command(slash_command, prefix_command)]
-pub async fn play(ctx: Context<'_>, query: String) -> Result<(), Error> {
+pub async fn play(
+    ctx: Context<'_>,
+    #[description = "A link, a search query, local:<path> or spotify:liked"] #[autocomplete = "autocomplete_query"] query: Option<String>,
+    #[description = "An audio file to play"] attachment: Option<Attachment>,
+    #[description = "What to queue for links to a video inside a playlist"] playlist: Option<PlaylistMode>,
+    #[description = "What to queue for Spotify artist links"] artist: Option<ArtistMode>,
+    #[description = "Where in the queue the tracks go, 1 plays them next"] #[min = 1] position: Option<usize>
+) -> Result<(), Error> {
+    play_inner(ctx, query, attachment, playlist, artist, position).await
+}
+
+/// Queues the query at `position`, or at the end of the queue without one
+pub async fn play_inner(
+    ctx: Context<'_>,
+    query: Option<String>,
+    attachment: Option<Attachment>,
+    playlist: Option<PlaylistMode>,
+    artist: Option<ArtistMode>,
+    position: Option<usize>
+) -> Result<(), Error> {
+    let typed_query = query.clone().filter(|_| attachment.is_none());
+    let query = match (query, attachment) {
+        (_, Some(attachment)) => {
+            let is_audio = attachment.content_type.as_deref().is_some_and(|content_type| content_type.starts_with("audio/") || content_type.starts_with("video/"));
+            if !is_audio {
+                send_error(&ctx, "That Attachment Isn't an Audio File").await;
+                return Ok(());
+            }
+            attachment.url
+        },
+        (Some(query), None) => query,
+        (None, None) => {
+            send_error(&ctx, "Give Me a Query Or Attach a File").await;
+            return Ok(());
+        }
+    };
+
     let guild = ctx.guild();
     if let Some(guild) = guild {
         let user_voice_state = guild.voice_states.get(&ctx.author().id);
         if let Some(user_voice_state) = user_voice_state {
-            let manager = songbird::get(&ctx.serenity_context()).await.unwrap();
-
-            let handler = match manager.get(guild.id) {
-                Some(handler) => handler,
-                None => manager.join(guild.id, user_voice_state.channel_id.unwrap()).await.0
-            };
-
-            let mut handler_guard = handler.lock().await;
-
-            handler_guard.add_global_event(Event::Track(TrackEvent::Play), MetadataEventHandler {handler: handler.clone(), channel_id: ctx.channel_id(), http: ctx.serenity_context().http.clone()});
-             
-            if user_voice_state.channel_id.map(|f| f.0) != handler_guard.current_channel().map(|f| f.0) {
+            let handler = join_call(&ctx, guild.id, user_voice_state.channel_id.unwrap()).await;
+            let current_channel = handler.lock().await.current_channel();
+            if user_voice_state.channel_id.map(|f| f.0) != current_channel.map(|f| f.0) {
                 send_error(&ctx, "You're In a Different Channel").await;
                 return Ok(());
             }
-            
-            if let Ok(inputs) = ctx.data().convert_query(&query).await {
-                let was_empty = handler_guard.queue().is_empty();
 
-                let mut handles: Vec<TrackHandle> = vec![];
-
-                for input in inputs {
-                    let (track, mut handle) = create_player(input);
-                    handle.write_added_by(&ctx.author()).await;
-                    handles.push(handle);
-                    handler_guard.enqueue(track);
+            // Resolving long playlists takes a while, so the call stays unlocked until there's something to queue
+            let inputs = match convert_with_progress(&ctx, &query, playlist.unwrap_or_default(), artist.unwrap_or_default()).await {
+                Ok(inputs) => inputs,
+                Err(err) => {
+                    send_error(&ctx, &describe_query_error(&err)).await;
+                    return Ok(());
                 }
+            };
+            // Empty playlists and albums resolve fine but leave nothing to queue
+            if inputs.is_empty() {
+                send_error(&ctx, &describe_query_error(&query_error!(NoSearchResults, query: query).into())).await;
+                return Ok(());
+            }
+            if let Some(ref typed_query) = typed_query {
+                ctx.data().autocomplete.record_query(guild.id.0, typed_query).await;
+            }
+            let mut handler_guard = handler.lock().await;
+            let was_empty = handler_guard.queue().is_empty();
+
+            let (handles, landed_position) = enqueue_inputs(&ctx, &mut handler_guard, inputs, position).await;
 
-                let mut now_playing_embed: Option<CreateEmbed> = None;
-                if was_empty {
-                    if let Some(mut track) = handler_guard.queue().current() {
-                        track.generate_lazy_metadata().await;
-                        if track.is_lazy() {
-                            if let Some(metadata) = track.read_lazy_metadata().await {
-                                now_playing_embed = Some(create_now_playing_embed(metadata, track.read_added_by().await));
-                            }
-                        } else {
-                            now_playing_embed = Some(create_now_playing_embed(MiniMetadata::lossy_from_metadata(track.metadata().clone()), track.read_added_by().await));
+            let mut now_playing_embed: Option<CreateEmbed> = None;
+            if was_empty {
+                if let Some(mut track) = handler_guard.queue().current() {
+                    track.generate_lazy_metadata().await;
+                    if track.is_lazy() {
+                        if let Some(metadata) = track.read_lazy_metadata().await {
+                            now_playing_embed = Some(create_now_playing_embed(metadata, track.read_added_by().await));
                         }
+                    } else {
+                        now_playing_embed = Some(create_now_playing_embed(MiniMetadata::lossy_from_metadata(track.metadata().clone()), track.read_added_by().await));
                     }
                 }
-                drop(handler_guard);
-                if handles.len() > 1 {
+            }
+            drop(handler_guard);
+            if handles.len() > 1 {
+                ctx.send(
+                    |msg| msg
+                        .ephemeral(true)
+                        .reply(true)
+                        .allowed_mentions(|s| s.replied_user(true))
+                        .embed(|embed| embed.title(format!("Added {} tracks", handles.len())).description(describe_position(landed_position, handles.len())).color(Color::PURPLE))
+                ).await?;
+            } else {
+                if let Some(handle) = handles.get(0) {
+                    let start_time = handle.metadata().start_time;
+                    let metadata = match handle.read_lazy_metadata().await {
+                        Some(lazy_metadata) => {
+                        lazy_metadata
+                        },
+                        None => {
+                            let metadata = handle.metadata();
+                            MiniMetadata::lossy_from_metadata(metadata.clone())
+                        }
+                    };
                     ctx.send(
                         |msg| msg
                             .ephemeral(true)
                             .reply(true)
                             .allowed_mentions(|s| s.replied_user(true))
-                            .embed(|embed| embed.title(format!("Added {} tracks", handles.len())).color(Color::PURPLE))
+                            .embed(|embed| {
+                                let mut description = format!("{} | {}", format_title(&metadata), format_metadata_duration(&metadata));
+                                if let Some(start_time) = start_time {
+                                    description += format!(" (from {})", format_duration(start_time, None)).as_str();
+                                }
+                                embed.title("Added track:").description(description).footer(|footer| footer.text(describe_position(landed_position, 1))).color(Color::PURPLE)
+                            })
                     ).await?;
-                } else {
-                    if let Some(handle) = handles.get(0) {
-                        let metadata = match handle.read_lazy_metadata().await {
-                            Some(lazy_metadata) => {
-                            lazy_metadata
-                            },
-                            None => {
-                                let metadata = handle.metadata();
-                                MiniMetadata::lossy_from_metadata(metadata.clone())
-                            }
-                        };
-                        ctx.send(
-                            |msg| msg
-                                .ephemeral(true)
-                                .reply(true)
-                                .allowed_mentions(|s| s.replied_user(true))
-                                .embed(|embed| embed.title("Added track:").description(format!("[{}]({}) | {}", metadata.title, metadata.source_url, format_duration(metadata.duration, None))).color(Color::PURPLE))
-                        ).await?;
-                    }
                 }
+            }
 
-                if let Some(now_playing_embed) = now_playing_embed {
-                    ctx.send(|message| message.embed(|embed| {embed.clone_from(&now_playing_embed); embed})).await;
-                }
-            } else {
-                send_error(&ctx, "Invalid Query").await;
-                return Ok(());
+            if let Some(now_playing_embed) = now_playing_embed {
+                ctx.send(|message| message.embed(|embed| {embed.clone_from(&now_playing_embed); embed})).await;
             }
         }
     }
     Ok(())
+}
+
+/// Where in the queue tracks landed, using the numbers `/queue` shows
+fn describe_position(position: usize, count: usize) -> String {
+    match (position, count) {
+        (0, 1) => "Playing now".to_owned(),
+        (0, _) => format!("Playing now, followed by positions 1-{}", count - 1),
+        (1, 1) => "Playing next".to_owned(),
+        (_, 1) => format!("Position {} in the queue", position),
+        (1, _) => format!("Positions 1-{} in the queue, playing next", count),
+        _ => format!("Positions {}-{} in the queue", position, position + count - 1)
+    }
+}
+
+/// The guild's recent queries, then YouTube results that submit the video's link
+pub async fn autocomplete_query(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice<String>> {
+    let autocomplete = &ctx.data().autocomplete;
+    let mut choices: Vec<AutocompleteChoice<String>> = vec![];
+    if let Some(guild_id) = ctx.guild_id() {
+        for recent_query in autocomplete.recent_queries(guild_id.0, partial).await {
+            // Longer values would make Discord reject every choice
+            if recent_query.chars().count() <= MAX_CHOICE_LENGTH {
+                choices.push(AutocompleteChoice { name: truncate(&format!("🕘 {}", recent_query), MAX_CHOICE_LENGTH), value: recent_query });
+            }
+        }
+    }
+
+    let partial = partial.trim();
+    if partial.chars().count() < MIN_SEARCH_LENGTH || !ctx.data().sources.is_search(partial) {
+        return choices;
+    }
+    let results = match autocomplete.cached_results(partial).await {
+        Some(results) => results,
+        None => {
+            // Every keystroke is its own request, only the one the user stopped at searches
+            if !autocomplete.debounce(ctx.author().id.0).await {
+                return choices;
+            }
+            autocomplete.search(partial).await.unwrap_or_default()
+        }
+    };
+    for result in results {
+        let url = result.url();
+        if !choices.iter().any(|choice| choice.value == url) {
+            let name = format!("{} | {}", result.title, format_result_duration(&result));
+            choices.push(AutocompleteChoice { name: truncate(&name, MAX_CHOICE_LENGTH), value: url });
+        }
+    }
+    choices.truncate(MAX_CHOICES);
+    choices
+}
+
+/// Converts the query while keeping a message updated with how many tracks long playlists have resolved so far
+async fn convert_with_progress(ctx: &Context<'_>, query: &str, playlist_mode: PlaylistMode, artist_mode: ArtistMode) -> Result<Vec<QueuedInput>, Error> {
+    let (progress, mut progress_receiver) = watch::channel(0);
+    let options = QueryOptions { playlist_mode, artist_mode, progress: Some(Arc::new(progress)), user_id: Some(ctx.author().id.0) };
+    let conversion = ctx.data().convert_query(query, options);
+    tokio::pin!(conversion);
+
+    let mut progress_message: Option<ReplyHandle> = None;
+    let mut last_update = Instant::now();
+    let mut is_reporting = true;
+    loop {
+        tokio::select! {
+            result = &mut conversion => {
+                if let Some(progress_message) = progress_message {
+                    let _ = progress_message.delete(*ctx).await;
+                }
+                return result;
+            },
+            changed = progress_receiver.changed(), if is_reporting => {
+                if changed.is_err() {
+                    is_reporting = false;
+                    continue;
+                }
+                // Editing on every page would hit the rate limit
+                if progress_message.is_some() && last_update.elapsed() < PROGRESS_UPDATE_INTERVAL {
+                    continue;
+                }
+                let resolved = *progress_receiver.borrow_and_update();
+                let title = format!("Resolving... {} tracks so far", resolved);
+                match progress_message {
+                    Some(ref progress_message) => {
+                        let _ = progress_message.edit(*ctx, |msg| msg.embed(|embed| embed.title(&title).color(Color::PURPLE))).await;
+                    },
+                    None => {
+                        progress_message = ctx.send(|msg| msg.ephemeral(true).embed(|embed| embed.title(&title).color(Color::PURPLE))).await.ok();
+                    }
+                }
+                last_update = Instant::now();
+            }
+        }
+    }
+}
+
+/// Enqueues the inputs in order at `position`, or at the end without one, returning their handles and where the first one landed
+pub async fn enqueue_inputs(ctx: &Context<'_>, handler_guard: &mut Call, inputs: Vec<QueuedInput>, position: Option<usize>) -> (Vec<TrackHandle>, usize) {
+    let mut handles: Vec<TrackHandle> = vec![];
+    let tail_position = handler_guard.queue().len();
+
+    for queued_input in inputs {
+        let (track, mut handle) = create_player(queued_input.input);
+        handle.write_added_by(&ctx.author()).await;
+        if let Some(track_data) = queued_input.track_data {
+            handle.write_track_data(track_data).await;
+        }
+        if let Some(source) = queued_input.source {
+            handle.write_track_source(source).await;
+        }
+        if let Some(stream_url) = queued_input.stream_url {
+            spawn(follow_stream_title(stream_url, handle.clone(), ctx.channel_id(), ctx.serenity_context().http.clone()));
+        }
+        handles.push(handle);
+        handler_guard.enqueue(track);
+    }
+    // songbird only appends, so the new tracks are moved up from the tail afterwards
+    let landed_position = match position {
+        Some(position) => handler_guard.queue().modify_queue(|queue| insert_enqueued(queue, handles.len(), position)),
+        None => tail_position
+    };
+    (handles, landed_position)
 }
\ No newline at end of file
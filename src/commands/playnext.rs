@@ -0,0 +1,14 @@
+use diwa_rs::{
+    Context,
+    error::Error
+};
+use super::play::play_inner;
+
+/// Queue a track right after the current one
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn playnext(
+    ctx: Context<'_>,
+    #[description = "A link, a search query, local:<path> or spotify:liked"] #[autocomplete = "super::play::autocomplete_query"] #[rest] query: String
+) -> Result<(), Error> {
+    play_inner(ctx, Some(query), None, None, None, Some(1)).await
+}
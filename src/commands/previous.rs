@@ -0,0 +1,62 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    LazyMetadataTrait,
+    utils::{send_error, send_reply, describe_query_error, format_title, join_call}
+};
+use super::play::enqueue_inputs;
+
+/// Play the last finished track again, skipping the current one
+#[poise::command(slash_command, prefix_command, aliases("back"), guild_only)]
+pub async fn previous(ctx: Context<'_>) -> Result<(), Error> {
+    if let Some(guild) = ctx.guild() {
+        if let Some(user_voice_state) = guild.voice_states.get(&ctx.author().id) {
+            let handler = join_call(&ctx, guild.id, user_voice_state.channel_id.unwrap()).await;
+            let mut handler_guard = handler.lock().await;
+
+            if user_voice_state.channel_id.map(|f| f.0) != handler_guard.current_channel().map(|f| f.0) {
+                send_error(&ctx, "You're In a Different Channel").await;
+                return Ok(());
+            }
+            let history = &ctx.data().history;
+            let entry = match history.take_latest(guild.id.0).await {
+                Some(entry) => entry,
+                None => {
+                    send_error(&ctx, "Nothing Has Finished Playing Yet").await;
+                    return Ok(());
+                }
+            };
+            let queued_input = match entry.source {
+                Some(source) => source.into_input().await,
+                None => {
+                    send_error(&ctx, "That Track Can't Be Played Again").await;
+                    return Ok(());
+                }
+            };
+            let queued_input = match queued_input {
+                Ok(queued_input) => queued_input,
+                Err(err) => {
+                    send_error(&ctx, &describe_query_error(&err)).await;
+                    return Ok(());
+                }
+            };
+
+            let current_track = handler_guard.queue().current();
+            let (mut handles, _) = enqueue_inputs(&ctx, &mut handler_guard, vec![queued_input], Some(1)).await;
+            if let Some(handle) = handles.get_mut(0) {
+                // Saves searching for the metadata again
+                if handle.is_lazy() {
+                    handle.write_lazy_metadata(entry.metadata.clone()).await;
+                }
+            }
+            if let Some(current_track) = current_track {
+                // Otherwise going back twice would return to the track skipped here
+                history.ignore(current_track.uuid()).await;
+                handler_guard.queue().skip()?;
+            }
+            drop(handler_guard);
+            send_reply(&ctx, &format!("Playing {} Again", format_title(&entry.metadata))).await;
+        }
+    }
+    Ok(())
+}
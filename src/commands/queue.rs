@@ -3,7 +3,8 @@ use diwa_rs::{
     error::Error,
     LazyMetadataTrait,
     MiniMetadata,
-    utils::format_duration
+    loop_mode::LoopMode,
+    utils::{format_duration, format_metadata_duration, format_title}
 };
 use poise::serenity_prelude::{ReactionType, MessageComponentInteraction};
 
@@ -16,7 +17,7 @@ use tokio::{
     task::{spawn, JoinHandle},
     sync::Mutex
 };
-use songbird::{Call, tracks::{TrackHandle, LoopState}};
+use songbird::{Call, tracks::TrackHandle};
 use serenity::utils::Color;
 use futures::stream::*;
 
@@ -28,7 +29,8 @@ pub async fn queue(ctx: Context<'_>, page: Option<u32>) -> Result<(), Error> {
         let manager = songbird::get(&ctx.serenity_context()).await.unwrap();
         if let Some(handler) = manager.get(guild.id) {
             let mut page = page.unwrap_or(0);
-            let (queue_embed, last_page) = assemble_embed(handler.clone(), page).await;
+            let loop_mode = ctx.data().loop_modes.get(guild.id.0).await;
+            let (queue_embed, last_page) = assemble_embed(handler.clone(), page, loop_mode).await;
             let mut last_page = last_page;
             let reply_handle = ctx.send(
                 |msg| msg
@@ -79,21 +81,13 @@ pub async fn extract_track_data(track: TrackHandle, include_play_time: bool) ->
     }
 }
 
-pub async fn assemble_embed(handler: Arc<Mutex<Call>>, page: u32) -> (CreateEmbed, u32) {
+pub async fn assemble_embed(handler: Arc<Mutex<Call>>, page: u32, loop_mode: LoopMode) -> (CreateEmbed, u32) {
     search_burst(handler.clone(), page).await;
     let handler_quard = handler.lock().await;
     let mut tracks_data: Vec<(MiniMetadata, Option<Duration>)> = vec![];
     let mut index = 0;
     let mut queue = handler_quard.queue().current_queue().into_iter().skip(1 + (TRACKS_PER_PAGE * page) as usize);
-    let mut is_looping = false;
     if let Some(current_track) = handler_quard.queue().current() {
-        if let Ok(info) = current_track.get_info().await {
-            if let LoopState::Infinite = info.loops {
-                is_looping = true;
-            } else {
-                is_looping = false;
-            }
-        }
         tracks_data.push(extract_track_data(current_track, true).await);
     }
     while let Some(track) = (&mut queue).next() {
@@ -104,39 +98,44 @@ pub async fn assemble_embed(handler: Arc<Mutex<Call>>, page: u32) -> (CreateEmbe
     let mut formatted_tracks: Vec<String> = vec![];
 
     for data in tracks_data {
-        formatted_tracks.push(format_track(data.0.title, data.0.source_url, data.0.duration, data.1))
+        formatted_tracks.push(format_track(data.0, data.1))
     }
 
     let last_page = ((handler_quard.queue().len() as f32 / TRACKS_PER_PAGE as f32).ceil() - 1.0).max(0.0) as u32;
-    (create_queue_embed(formatted_tracks, page, last_page, handler_quard.queue().len(), is_looping), last_page)
+    (create_queue_embed(formatted_tracks, page, last_page, handler_quard.queue().len(), loop_mode), last_page)
 }
 
 pub async fn search_burst(handler: Arc<Mutex<Call>>, page: u32) {
-    let handler_guard = handler.lock().await;
+    let tracks: Vec<TrackHandle> = handler.lock().await.queue().current_queue().into_iter()
+        .skip(1 + (TRACKS_PER_PAGE * page) as usize)
+        .take(TRACKS_PER_PAGE as usize)
+        .collect();
+    resolve_lazy_metadata(tracks).await;
+}
 
-    let mut handles: Vec<JoinHandle<()>> = vec![];
-    let mut index = 0;
-    let mut queue = handler_guard.queue().current_queue().into_iter().skip(1 + (TRACKS_PER_PAGE * page) as usize);
-    while let Some(track) = &mut queue.next() {
-        if index == TRACKS_PER_PAGE {break;}
-        let mut cloned_track = track.clone();
-        handles.push(spawn(async move {
-            if cloned_track.is_lazy() {
-                if let None = cloned_track.read_lazy_metadata().await {
-                    cloned_track.generate_lazy_metadata().await;
+/// Generates the metadata of lazy tracks that don't have it yet, a burst of them at a time
+pub async fn resolve_lazy_metadata(tracks: Vec<TrackHandle>) {
+    for burst in tracks.chunks(TRACKS_PER_PAGE as usize) {
+        let mut handles: Vec<JoinHandle<()>> = vec![];
+        for track in burst {
+            let mut cloned_track = track.clone();
+            handles.push(spawn(async move {
+                if cloned_track.is_lazy() {
+                    if let None = cloned_track.read_lazy_metadata().await {
+                        cloned_track.generate_lazy_metadata().await;
+                    }
                 }
-            }
-        }));
-        index += 1;
-    }
-    for handle in handles {
-        handle.await;
+            }));
+        }
+        for handle in handles {
+            handle.await;
+        }
     }
 }
 
-pub fn create_queue_embed(tracks: Vec<String>, page: u32, last_page: u32, tracks_len: usize, is_looping: bool) -> CreateEmbed {
+pub fn create_queue_embed(tracks: Vec<String>, page: u32, last_page: u32, tracks_len: usize, loop_mode: LoopMode) -> CreateEmbed {
     let mut embed = CreateEmbed::default();
-    embed.title("Queue").footer(|footer| footer.text(format!("Page: {}/{}  tracks: {}  loop: {}", page + 1, last_page.max(1), tracks_len, is_looping)));
+    embed.title("Queue").footer(|footer| footer.text(format!("Page: {}/{}  tracks: {}  loop: {}", page + 1, last_page.max(1), tracks_len, loop_mode)));
     let mut next_up = String::new();
     if let Some(current_track) = tracks.first() {
         embed.field("Currently Playing:", current_track, false);
@@ -155,11 +154,11 @@ pub fn create_queue_embed(tracks: Vec<String>, page: u32, last_page: u32, tracks
     embed
 }
 
-pub fn format_track(title: String, source_url: String, duration: Duration, play_time: Option<Duration>) -> String {
+pub fn format_track(metadata: MiniMetadata, play_time: Option<Duration>) -> String {
     let mut formatted_track = String::new();
-    formatted_track += format!("**[{}]({})** | ", title, source_url).as_str();
-    let formatted_duration = format_duration(duration, None);
-    if let Some(play_time) = play_time {
+    formatted_track += format!("**{}** | ", format_title(&metadata)).as_str();
+    let formatted_duration = format_metadata_duration(&metadata);
+    if let (Some(play_time), false) = (play_time, metadata.is_live) {
         let mut formatted_play_time = format_duration(play_time, Some(formatted_duration.len() as u32));
         formatted_play_time.push('/');
         formatted_track += formatted_play_time.as_str();
@@ -180,7 +179,11 @@ pub async fn update_queue_embed(page: u32, last_page: &mut u32, message_collecto
     let channel_id = message_collector.message.channel_id.0;
     let message_id = message_collector.message.id.0;
     if let Ok(mut message) = ctx.serenity_context().http.get_message(channel_id, message_id).await {
-        let (new_queue_embed, new_last_page) = assemble_embed(handler, page).await;
+        let loop_mode = match ctx.guild_id() {
+            Some(guild_id) => ctx.data().loop_modes.get(guild_id.0).await,
+            None => LoopMode::Off
+        };
+        let (new_queue_embed, new_last_page) = assemble_embed(handler, page, loop_mode).await;
         *last_page = new_last_page;
         message.edit(ctx, |f| f.set_embed(new_queue_embed).components(|components| components.set_action_row(create_buttons(page, *last_page)))).await;
         message_collector.defer(ctx).await;
@@ -0,0 +1,34 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    youtube_quota::seconds_until_reset,
+    utils::format_duration
+};
+use serenity::utils::Color;
+use std::time::Duration;
+
+/// Show how much of today's YouTube Data API quota was used
+#[poise::command(slash_command, prefix_command, required_permissions = "ADMINISTRATOR", default_member_permissions = "ADMINISTRATOR")]
+pub async fn quota(ctx: Context<'_>) -> Result<(), Error> {
+    let quota = ctx.data().youtube_quota.clone();
+    let usage = quota.usage().await;
+    let mut calls = usage.calls.iter()
+        .map(|(endpoint, calls)| format!("`{}` {} calls", endpoint, calls))
+        .collect::<Vec<String>>()
+        .join("\n");
+    if calls.is_empty() {
+        calls = "No calls yet".to_owned();
+    }
+    let status = if usage.units >= quota.threshold { "Using yt-dlp until the reset" } else { "Using the Data API" };
+
+    ctx.send(|msg| msg
+        .ephemeral(true)
+        .embed(|embed| embed
+            .title("YouTube Quota")
+            .description(format!("**{}/{}** units used, the bot switches to yt-dlp at **{}**\n{}", usage.units, quota.limit, quota.threshold, status))
+            .field("Calls", calls, false)
+            .footer(|footer| footer.text(format!("Resets in {}", format_duration(Duration::from_secs(seconds_until_reset()), None))))
+            .color(Color::PURPLE))
+    ).await?;
+    Ok(())
+}
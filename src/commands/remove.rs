@@ -0,0 +1,38 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    queue_edit::{parse_positions, remove_tracks},
+    utils::{author_call, send_error, send_reply, track_title}
+};
+
+/// Remove a track from the queue, or a range of them like 3-7
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "A track number from /queue or a range like 3-7"] positions: String
+) -> Result<(), Error> {
+    let positions = match parse_positions(&positions) {
+        Ok(positions) => positions,
+        Err(err) => {
+            send_error(&ctx, &err.user_description()).await;
+            return Ok(());
+        }
+    };
+    if let Some(handler) = author_call(&ctx).await {
+        let removed = handler.lock().await.queue().modify_queue(|queue| remove_tracks(queue, positions));
+        match removed {
+            Ok(removed) => {
+                // Queued tracks already sit paused in the driver, they'd stay there without being stopped
+                for track in removed.iter() {
+                    let _ = track.stop();
+                }
+                match removed.first() {
+                    Some(track) if removed.len() == 1 => send_reply(&ctx, &format!("Removed {}", track_title(&track.handle()).await)).await,
+                    _ => send_reply(&ctx, &format!("Removed {} Tracks", removed.len())).await
+                }
+            },
+            Err(err) => send_error(&ctx, &err.user_description()).await
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    utils::{send_error, describe_query_error, format_result_duration, truncate, join_call},
+    sources::QueuedItem,
+    convert_query::LazyQueued,
+    youtube_scraper::{search as search_youtube, SearchResult}
+};
+use serenity::utils::Color;
+use std::time::Duration;
+use super::play::enqueue_inputs;
+
+const MAX_RESULTS: usize = 8;
+const PICK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Discord's limit for select menu labels and descriptions
+const MAX_OPTION_LENGTH: usize = 100;
+
+/// Pick which YouTube result to play
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "What to search YouTube for"] #[rest] query: String
+) -> Result<(), Error> {
+    if let Some(guild) = ctx.guild() {
+        if let Some(user_voice_state) = guild.voice_states.get(&ctx.author().id) {
+            ctx.defer_ephemeral().await?;
+            let results: Vec<SearchResult> = match search_youtube(&query).await {
+                Ok(results) => results.into_iter().take(MAX_RESULTS).collect(),
+                Err(err) => {
+                    println!("Couldn't search YouTube for {}: {}", query, err);
+                    send_error(&ctx, &describe_query_error(&err)).await;
+                    return Ok(());
+                }
+            };
+            if results.is_empty() {
+                send_error(&ctx, &format!("Nothing Was Found For `{}`", query)).await;
+                return Ok(());
+            }
+
+            let reply_handle = ctx.send(
+                |msg| msg
+                    .ephemeral(true)
+                    .embed(|embed| embed
+                        .title(format!("Results for {}", query))
+                        .description(results.iter().enumerate().map(|(index, result)| format!("{}. {}", index + 1, format_result(result))).collect::<Vec<String>>().join("\n"))
+                        .footer(|footer| footer.text(format!("Pick a track within {} seconds", PICK_TIMEOUT.as_secs())))
+                        .color(Color::PURPLE))
+                    .components(|components| components.create_action_row(|row| row.create_select_menu(|menu| menu
+                        .custom_id("search_result")
+                        .placeholder("Pick a track")
+                        .options(|options| {
+                            for (index, result) in results.iter().enumerate() {
+                                options.create_option(|option| option
+                                    .label(truncate(&format!("{}. {}", index + 1, result.title), MAX_OPTION_LENGTH))
+                                    .description(truncate(&format!("{} | {}", result.channel.as_deref().unwrap_or("Unknown Channel"), format_result_duration(result)), MAX_OPTION_LENGTH))
+                                    .value(index));
+                            }
+                            options
+                        }))))
+            ).await?;
+
+            let interaction = reply_handle.message().await?.await_component_interaction(ctx).timeout(PICK_TIMEOUT).author_id(ctx.author().id).await;
+            let result = match interaction.as_ref().and_then(|interaction| interaction.data.values.first()).and_then(|value| value.parse::<usize>().ok()).and_then(|index| results.get(index)) {
+                Some(result) => result,
+                None => {
+                    ctx.data().delete_after_delay(reply_handle, Duration::ZERO).await;
+                    return Ok(());
+                }
+            };
+            if let Some(interaction) = interaction.as_ref() {
+                let _ = interaction.defer(ctx).await;
+            }
+
+            let input = match QueuedItem::new(LazyQueued::Lazy(result.metadata(), None)).into_input().await {
+                Ok(input) => input,
+                Err(err) => {
+                    send_error(&ctx, &describe_query_error(&err)).await;
+                    return Ok(());
+                }
+            };
+
+            let handler = join_call(&ctx, guild.id, user_voice_state.channel_id.unwrap()).await;
+            let mut handler_guard = handler.lock().await;
+
+            if user_voice_state.channel_id.map(|f| f.0) != handler_guard.current_channel().map(|f| f.0) {
+                send_error(&ctx, "You're In a Different Channel").await;
+                return Ok(());
+            }
+
+            enqueue_inputs(&ctx, &mut handler_guard, vec![input], None).await;
+            drop(handler_guard);
+
+            reply_handle.edit(ctx, |msg| msg
+                .embed(|embed| embed.title("Added track:").description(format_result(result)).color(Color::PURPLE))
+                .components(|components| components)
+            ).await?;
+        } else {
+            send_error(&ctx, "You're Not In a Voice Channel").await;
+        }
+    }
+    Ok(())
+}
+
+fn format_result(result: &SearchResult) -> String {
+    format!("[{}]({}) | {} | {}", result.title, result.url(), result.channel.as_deref().unwrap_or("Unknown Channel"), format_result_duration(result))
+}
@@ -0,0 +1,23 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    queue_edit::shuffle_queue,
+    utils::{author_call, send_error, send_reply}
+};
+
+/// Shuffle the queue, the current track keeps playing
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn shuffle(ctx: Context<'_>) -> Result<(), Error> {
+    if let Some(handler) = author_call(&ctx).await {
+        let handler_guard = handler.lock().await;
+        let queued = handler_guard.queue().len().saturating_sub(1);
+        handler_guard.queue().modify_queue(|queue| shuffle_queue(queue, &mut rand::thread_rng()));
+        drop(handler_guard);
+        if queued < 2 {
+            send_error(&ctx, "There's Nothing To Shuffle").await;
+        } else {
+            send_reply(&ctx, &format!("Shuffled {} Tracks", queued)).await;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    queue_edit::skip_to,
+    utils::{author_call, send_error, send_reply, track_title}
+};
+
+/// Skip straight to a queued track, dropping the ones before it
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn skipto(
+    ctx: Context<'_>,
+    #[description = "The track number from /queue"] position: usize
+) -> Result<(), Error> {
+    if let Some(handler) = author_call(&ctx).await {
+        let handler_guard = handler.lock().await;
+        let skipped = handler_guard.queue().modify_queue(|queue| skip_to(queue, position));
+        match skipped {
+            Ok(skipped) => {
+                for track in skipped.iter() {
+                    let _ = track.stop();
+                }
+                let next_track = handler_guard.queue().current_queue().get(1).cloned();
+                // Ending the current track makes songbird start the new second one
+                handler_guard.queue().skip()?;
+                drop(handler_guard);
+                if let Some(next_track) = next_track {
+                    send_reply(&ctx, &format!("Skipped To {}", track_title(&next_track).await)).await;
+                }
+            },
+            Err(err) => {
+                drop(handler_guard);
+                send_error(&ctx, &err.user_description()).await;
+            }
+        }
+    }
+    Ok(())
+}
@@ -2,7 +2,8 @@ use diwa_rs::{
     Context,
     error::Error,
     LazyMetadataTrait,
-    utils::format_duration
+    MiniMetadata,
+    utils::{format_duration, format_metadata_duration, format_title}
 };
 use poise::ReplyHandle;
 
@@ -22,16 +23,13 @@ pub async fn song(ctx: Context<'_>) -> Result<(), Error> {
                     if current_track.is_lazy() {
                         if let Some(metadata) = current_track.read_lazy_metadata().await {
                             let play_time = current_track_state.play_time;
-                            let reply_handle = send_msg(&ctx, metadata.title, metadata.source_url, play_time, metadata.duration).await?;
+                            let reply_handle = send_msg(&ctx, metadata, play_time).await?;
                             ctx.data().delete_after_delay(reply_handle, Duration::from_secs(15)).await;
                         }
                     } else {
-                        let metadata = current_track.metadata();
-                        let title = metadata.title.clone().unwrap_or("Error".to_owned());
-                        let source_url = metadata.source_url.clone().unwrap_or("".to_owned());
-                        let duration = metadata.duration.clone().unwrap_or(Duration::ZERO);
+                        let metadata = MiniMetadata::lossy_from_metadata(current_track.metadata().clone());
                         let play_time = current_track_state.play_time;
-                        let reply_handle = send_msg(&ctx, title, source_url, play_time, duration).await?;
+                        let reply_handle = send_msg(&ctx, metadata, play_time).await?;
                         ctx.data().delete_after_delay(reply_handle, Duration::from_secs(15)).await;
                     }
                 }
@@ -52,16 +50,20 @@ pub async fn song(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-async fn send_msg<'a>(ctx: &'a Context<'_>, title: String, source_url: String, play_time: Duration, duration: Duration) -> Result<ReplyHandle<'a>, Error> {
-    let formatted_duration = format_duration(duration, None);
-    let formatted_play_time = format_duration(play_time, Some(formatted_duration.len() as u32));
+async fn send_msg<'a>(ctx: &'a Context<'_>, metadata: MiniMetadata, play_time: Duration) -> Result<ReplyHandle<'a>, Error> {
+    let formatted_duration = format_metadata_duration(&metadata);
+    let formatted_time = if metadata.is_live {
+        formatted_duration
+    } else {
+        format!("{}/{}", format_duration(play_time, Some(formatted_duration.len() as u32)), formatted_duration)
+    };
     let reply_handle = ctx.send(
         |msg| msg
         .ephemeral(true)
         .allowed_mentions(|s| s.replied_user(true))
         .embed(|embed| embed
             .title("Currently Playing:")
-            .description(format!("[{}]({}) | {}/{}", title, source_url, formatted_play_time, formatted_duration))
+            .description(format!("{} | {}", format_title(&metadata), formatted_time))
             .color(Color::PURPLE))
         ).await?;
     Ok(reply_handle)
@@ -0,0 +1,53 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    AddedBy,
+    queue_edit::{sort_queue, SortOrder},
+    utils::{author_call, send_reply}
+};
+use std::{collections::HashMap, time::Duration};
+use super::queue::{extract_track_data, resolve_lazy_metadata};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Text(String),
+    Length(Duration)
+}
+
+/// Sort the queue, the current track keeps playing
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn sort(
+    ctx: Context<'_>,
+    #[description = "What to sort the queue by"] order: SortOrder
+) -> Result<(), Error> {
+    if let Some(handler) = author_call(&ctx).await {
+        ctx.defer_ephemeral().await?;
+        let tracks = handler.lock().await.queue().current_queue();
+        if order != SortOrder::AddedBy {
+            // Lazy tracks only have a title and duration once their metadata is generated
+            resolve_lazy_metadata(tracks.clone()).await;
+        }
+
+        let mut keys = HashMap::new();
+        for track in tracks {
+            let key = match order {
+                SortOrder::Title => {
+                    let title = extract_track_data(track.clone(), false).await.0.title.to_lowercase();
+                    Some(title).filter(|title| !title.is_empty()).map(SortKey::Text)
+                },
+                SortOrder::Duration => {
+                    let metadata = extract_track_data(track.clone(), false).await.0;
+                    Some(SortKey::Length(metadata.duration)).filter(|_| !metadata.is_live)
+                },
+                SortOrder::AddedBy => track.read_added_by().await.map(|user| SortKey::Text(user.name.to_lowercase()))
+            };
+            if let Some(key) = key {
+                keys.insert(track.uuid(), key);
+            }
+        }
+
+        handler.lock().await.queue().modify_queue(|queue| sort_queue(queue, |track| keys.get(&track.uuid()).cloned()));
+        send_reply(&ctx, &format!("Sorted The Queue By {}", order.name())).await;
+    }
+    Ok(())
+}
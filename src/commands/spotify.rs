@@ -0,0 +1,59 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    spotify_accounts::LINK_TIMEOUT,
+    utils::{send_error, send_reply}
+};
+use poise::serenity_prelude::ButtonStyle;
+use serenity::utils::Color;
+use tokio::time::timeout;
+
+#[poise::command(slash_command, subcommands("link", "unlink"))]
+pub async fn spotify(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Link your Spotify account to queue private playlists and spotify:liked
+#[poise::command(slash_command)]
+pub async fn link(ctx: Context<'_>) -> Result<(), Error> {
+    let accounts = ctx.data().spotify_accounts.clone();
+    let (authorize_url, state, linked) = accounts.start_link(ctx.author().id.0).await?;
+
+    let reply_handle = ctx.send(
+        |msg| msg
+            .ephemeral(true)
+            .embed(|embed| embed
+                .title("Link Spotify")
+                .description(format!("Authorize the bot within {} minutes, this message updates once you're done", LINK_TIMEOUT.as_secs() / 60))
+                .color(Color::PURPLE))
+            .components(|components| components.create_action_row(|row| row.create_button(|button| button
+                .label("Open Spotify")
+                .style(ButtonStyle::Link)
+                .url(&authorize_url))))
+    ).await?;
+
+    let (title, description, color) = match timeout(LINK_TIMEOUT, linked).await {
+        Ok(Ok(Ok(display_name))) => ("Spotify Linked", format!("Linked as **{}**, `spotify:liked` and your private playlists now work with `/play`", display_name), Color::PURPLE),
+        Ok(Ok(Err(_))) => ("Error", "Spotify Didn't Authorize The Link".to_owned(), Color::RED),
+        _ => {
+            accounts.cancel_link(&state).await;
+            ("Error", "The Link Expired".to_owned(), Color::RED)
+        }
+    };
+    reply_handle.edit(ctx, |msg| msg
+        .embed(|embed| embed.title(title).description(description).color(color))
+        .components(|components| components)
+    ).await?;
+    Ok(())
+}
+
+/// Forget your linked Spotify account
+#[poise::command(slash_command)]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    if ctx.data().spotify_accounts.unlink(ctx.author().id.0).await? {
+        send_reply(&ctx, "Unlinked Your Spotify Account").await;
+    } else {
+        send_error(&ctx, "You Haven't Linked a Spotify Account").await;
+    }
+    Ok(())
+}
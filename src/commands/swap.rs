@@ -0,0 +1,23 @@
+use diwa_rs::{
+    Context,
+    error::Error,
+    queue_edit::swap_tracks,
+    utils::{author_call, send_error, send_reply}
+};
+
+/// Swap two queued tracks
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn swap(
+    ctx: Context<'_>,
+    #[description = "A track number from /queue"] first: usize,
+    #[description = "The track number to swap it with"] second: usize
+) -> Result<(), Error> {
+    if let Some(handler) = author_call(&ctx).await {
+        let swapped = handler.lock().await.queue().modify_queue(|queue| swap_tracks(queue, first, second));
+        match swapped {
+            Ok(_) => send_reply(&ctx, &format!("Swapped Tracks {} And {}", first, second)).await,
+            Err(err) => send_error(&ctx, &err.user_description()).await
+        }
+    }
+    Ok(())
+}
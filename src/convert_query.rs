@@ -1,109 +1,109 @@
 //use std::error::Error;
 use crate::{
-    missing_value, url_error, Data,
-    error::Error
+    missing_value, query_error, Data,
+    error::Error,
+    spotify_to_query::{ TrackData, ResolveProgress },
+    track_matcher::MatchedCandidate,
+    sources::QueuedItem
 };
 
-use url::Url;
+use url::{Url, ParseError};
 use songbird::{
     input::{
-        Metadata, Input, ytdl_search, Codec, Container, ytdl, Restartable,
-        restartable::Restart
+        Metadata, Input, ytdl_search, Codec, Container, ytdl, ffmpeg, ffmpeg_optioned,
+        restartable::Restart, error::Error as InputError
     }
 };
 use poise::async_trait;
 use tokio::process::Command;
-use std::process::Stdio;
-
-#[derive(Debug, Clone)]
-pub enum Media {
-    YouTubeVideo(String),
-    YouTubePlaylist(String),
-    SpotifyTrack(String),
-    SpotifyPlaylist(String),
-    SpotifyAlbum(String),
-    Search(String)
-}
-
-pub fn extract_media(data: &Data, query: &str) -> Result<Media, Error> {
-    let url = Url::parse(query.into())?;
-    return Ok(match url.domain().unwrap() {
-        "www.youtube.com" | "youtube.com" => {
-            let video_id = url.query_pairs().into_owned().find(|p| p.0 == "v").map(|f| f.1);
-
-            let result = if let Some(video_id) = video_id {
-                Media::YouTubeVideo(video_id)
-            } else if let Some(playlist_id) = url.query_pairs().into_owned().find(|p| p.0 == "list").map(|f| f.1) {
-                Media::YouTubePlaylist(playlist_id)
-            } else { return Err(url_error!("Incorrect Url Format").into()) };
-            result
-        },
-        "www.youtu.be" | "youtu.be" => {
-            let result = if let Some(video_id) = url.path().strip_prefix("/").map(|f| f.to_owned()) {
-                Media::YouTubeVideo(video_id)
-            } else { return Err(url_error!("Incorrect Url Format").into()) };
-            result
-        },
-        "open.spotify.com" | "www.open.spotify.com" => {
-            let argumets = url.path_segments().map(|f| f.collect::<Vec<&str>>()).ok_or(url_error!("Parsing Error"))?;
-            let content_type = argumets.get(0);
-            let id = argumets.get(1);
-
-            if let Some(content_type) = content_type {
-                if let Some(id) = id {
-                    let result = match *content_type {
-                        "track" => Media::SpotifyTrack((*id).to_owned()),
-                        "playlist" => Media::SpotifyPlaylist((*id).to_owned()),
-                        "album" => Media::SpotifyAlbum((*id).to_owned()),
-                        _ => return Err(url_error!("Unknown Content Type").into())
-                    };
-                    return Ok(result);
-                }
-            }
+use std::{process::Stdio, time::Duration};
 
-            return Err(url_error!("Required Arguments Missing").into());
-        }
-        _ => Media::Search(query.to_owned())
-    });
-}
-
-pub async fn convert_query(data: &Data, query: &str) -> Result<Vec<Input>, Error> {
-    let media = extract_media(data, query)?;
-    
-    return Ok(match media {
-        Media::YouTubeVideo(id) => {
-            let video_metadata = data.extract_youtube_video_metadata(&id).await?;
-            let restartable = Restartable::new(LazyQueued::Lazy(video_metadata), true).await?;
-            vec![restartable.into()]
-        },
-        Media::YouTubePlaylist(id) => {
-            let playlist_metadata = data.extract_youtube_playlist_metadata(&id).await?;
-            let mut inputs: Vec<Input> = vec![];
-            for video_metadata in playlist_metadata {
-                let restartable = Restartable::new(LazyQueued::Lazy(video_metadata), true).await?;
-                inputs.push(restartable.into());
-            }
-            inputs
+static FFMPEG_ARGS: [&str; 9] = ["-f", "s16le", "-ac", "2", "-ar", "48000", "-acodec", "pcm_f32le", "-"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, poise::ChoiceParameter)]
+pub enum PlaylistMode {
+    #[default]
+    #[name = "video only"]
+    VideoOnly,
+    #[name = "whole playlist"]
+    WholePlaylist
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, poise::ChoiceParameter)]
+pub enum ArtistMode {
+    #[default]
+    #[name = "top tracks"]
+    TopTracks,
+    #[name = "latest album"]
+    LatestAlbum
+}
+
+/// Everything that shapes how a query is resolved besides the query itself
+#[derive(Default)]
+pub struct QueryOptions {
+    pub playlist_mode: PlaylistMode,
+    pub artist_mode: ArtistMode,
+    pub progress: Option<ResolveProgress>,
+    /// Whose linked Spotify account to read private playlists with
+    pub user_id: Option<u64>
+}
+
+pub struct QueuedInput {
+    pub input: Input,
+    pub track_data: Option<TrackData>,
+    /// Set for internet radio, whose current song is followed through ICY metadata
+    pub stream_url: Option<String>,
+    /// What the input was made from, kept so the track can be queued again
+    pub source: Option<QueuedItem>
+}
+
+impl From<Input> for QueuedInput {
+    fn from(input: Input) -> Self {
+        Self { input, track_data: None, stream_url: None, source: None }
+    }
+}
+
+/// The host of a link, `None` for plain text and anything that isn't a valid link
+pub fn url_host(query: &str) -> Option<String> {
+    match parse_url(query) {
+        Some(Ok(url)) => url.host_str().map(|host| host.to_ascii_lowercase()),
+        _ => None
+    }
+}
+
+/// Returns `None` when the query is plain text rather than a link
+pub fn parse_url(query: &str) -> Option<Result<Url, Error>> {
+    if query.is_empty() || query.contains(char::is_whitespace) {
+        return None;
+    }
+
+    match Url::parse(query) {
+        // Text like `artist:song` parses as a url with an unknown scheme, but isn't meant as a link
+        Ok(url) if url.cannot_be_a_base() => None,
+        Ok(url) => match url.scheme() {
+            "http" | "https" => Some(Ok(url)),
+            scheme => Some(Err(query_error!(UnsupportedScheme, scheme: scheme).into()))
         },
-        Media::SpotifyTrack(id) => {
-            let track_data = data.extract_spotify_track_query(&id).await?;
-            vec![ytdl_search(format!("{} by {}", track_data.title, track_data.artists.join(", "))).await?]
+        // Links pasted without a scheme, e.g. `youtu.be/dQw4w9WgXcQ`
+        Err(ParseError::RelativeUrlWithoutBase) if query.contains('.') && query.contains('/') => {
+            Some(Url::parse(&format!("https://{}", query)).map_err(|err| query_error!(MalformedUrl, description: err.to_string()).into()))
         },
-        Media::SpotifyPlaylist(id) | Media::SpotifyAlbum(id) => {
-            let playlist_data = data.extract_spotify_playlist_queries(&id).await?;
-            let mut inputs: Vec<Input> = vec![];
+        Err(ParseError::RelativeUrlWithoutBase) => None,
+        Err(err) => Some(Err(query_error!(MalformedUrl, description: err.to_string()).into()))
+    }
+}
 
-            for track_data in playlist_data {
-                let restartable = Restartable::new(LazyQueued::Lazier(format!("{} by {}", track_data.title, track_data.artists.join(", "))), true).await?;
-                inputs.push(restartable.into());
-            }
-            inputs
-        }
-        Media::Search(search_query) => {
-            vec![Restartable::ytdl_search(search_query, true).await?.into()]
-        }
-    });
+pub async fn convert_query(data: &Data, query: &str, options: QueryOptions) -> Result<Vec<QueuedInput>, Error> {
+    let mut inputs: Vec<QueuedInput> = vec![];
+    for item in data.sources.resolve(query, &options).await? {
+        inputs.push(item.into_input().await?);
+    }
+    Ok(inputs)
+}
 
+/// Queues a search that's only resolved once it's about to play
+pub async fn lazier_input(query: String) -> Result<QueuedInput, Error> {
+    QueuedItem::new(LazyQueued::Lazier(query)).into_input().await
 }
 
 pub async fn ytdl_search_metadata(query: &str) -> Result<Metadata, Error> {
@@ -118,17 +118,85 @@ pub async fn ytdl_search_metadata(query: &str) -> Result<Metadata, Error> {
     Ok(Metadata::from_ytdl_output(value))
 }
 
+/// Starts the audio of `uri` at `time`, songbird's own seeking ytdl helper isn't public
+async fn ytdl_from(uri: &str, time: Duration) -> songbird::input::error::Result<Input> {
+    let mut cmd = Command::new("yt-dlp");
+    cmd.args::<Vec<&str>, &str>(vec![
+        "-g", "-f", "webm[abr>0]/bestaudio/best", "-R", "infinite", "--no-playlist", "--ignore-config", "--no-warnings", uri
+    ]);
+    let out = cmd.stdin(Stdio::null()).output().await?;
+    let stream_url = String::from_utf8_lossy(&out.stdout).lines().next().map(|line| line.to_owned()).ok_or(InputError::Metadata)?;
+
+    let timestamp = format!("{:.3}", time.as_secs_f64());
+    ffmpeg_optioned(stream_url, &["-ss", &timestamp], &FFMPEG_ARGS).await
+}
+
+pub async fn ffprobe_metadata(path: &str) -> Result<Metadata, Error> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args::<Vec<&str>, &str>(vec![
+        "-v", "quiet", "-of", "json", "-show_format", "-show_streams", "-i", path
+    ]);
+    let out = cmd.stdin(Stdio::null()).output().await?;
+
+    let value = serde_json::from_slice::<serde_json::Value>(&out.stdout)?;
+
+    Ok(Metadata::from_ffprobe_json(&value))
+}
+
+/// Asks yt-dlp about any supported link, playlists and sets come back as one entry per track
+pub async fn ytdl_url_metadata(url: &str) -> Result<Vec<Metadata>, Error> {
+    let mut cmd = Command::new("yt-dlp");
+    cmd.args::<Vec<&str>, &str>(vec![
+        "-j", "--simulate", "--flat-playlist", "-R", "infinite", "--ignore-config", "--no-warnings", url
+    ]);
+    let out = cmd.stdin(Stdio::null()).output().await?;
+    if !out.status.success() {
+        return Err(query_error!(UnsupportedUrl, url: url).into());
+    }
+
+    let mut metadata_collector: Vec<Metadata> = vec![];
+    for line in out.stdout.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()) {
+        let value = serde_json::from_slice::<serde_json::Value>(line)?;
+        // Flat playlist entries only carry `url`, full entries carry `webpage_url`
+        let is_live = value.get("is_live").and_then(serde_json::Value::as_bool).unwrap_or(false);
+        let entry_url = value.get("url").and_then(serde_json::Value::as_str).map(|url| url.to_owned());
+
+        let mut metadata = Metadata::from_ytdl_output(value);
+        metadata.source_url = metadata.source_url.or(entry_url);
+        if !is_live {
+            metadata.duration = metadata.duration.or(Some(Duration::ZERO));
+        }
+        metadata.channels = Some(2);
+        metadata.sample_rate = Some(48000);
+        if metadata.source_url.is_some() {
+            metadata_collector.push(metadata);
+        }
+    }
+
+    if metadata_collector.is_empty() {
+        return Err(query_error!(UnsupportedUrl, url: url).into());
+    }
+    Ok(metadata_collector)
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum LazyQueued {
-    Lazy(Metadata),
-    Lazier(String)
+    Lazy(Metadata, Option<Duration>),
+    Lazier(String),
+    /// Plain text searches, whose metadata is looked up when they're queued
+    Search(String),
+    /// Spotify tracks, matched to a YouTube video once they're about to play
+    Matched(TrackData, MatchedCandidate),
+    /// Audio files, radio streams and local files played by ffmpeg directly
+    Direct(Metadata)
 }
 
 impl LazyQueued {
-    fn new_lazy(metadata: Metadata) -> Result<Self, Error> {
+    fn new_lazy(metadata: Metadata, start_time: Option<Duration>) -> Result<Self, Error> {
         if metadata.source_url.is_none() {
             return Err(missing_value!("source_url").into());
         }
-        Ok(LazyQueued::Lazy(metadata))
+        Ok(LazyQueued::Lazy(metadata, start_time))
     }
 
     fn new_lazier(query: String) -> Result<Self, Error> {
@@ -136,32 +204,113 @@ impl LazyQueued {
     }
 }
 
+/// Placeholder metadata until `generate_lazy_metadata` finds out what's actually playing
+fn lazy_metadata(title: String) -> Metadata {
+    let mut metadata = Metadata::default();
+    metadata.channels = Some(2);
+    metadata.sample_rate = Some(48000);
+    metadata.title = Some(title);
+    metadata.duration = Some(Duration::ZERO);
+    metadata.track = Some("$lazy_metadata$".to_owned());
+    metadata
+}
+
 #[async_trait]
 impl Restart for LazyQueued {
-    async fn call_restart(&mut self, time: Option<std::time::Duration>) -> songbird::input::error::Result<Input> {
+    async fn call_restart(&mut self, time: Option<Duration>) -> songbird::input::error::Result<Input> {
         match *self {
-            LazyQueued::Lazy(ref metadata) => {
-                return ytdl(metadata.source_url.clone().unwrap()).await;
+            LazyQueued::Lazy(ref metadata, ref mut start_time) => {
+                let source_url = metadata.source_url.clone().unwrap();
+                // The first restart always asks for the beginning of the track, which is where the start offset applies.
+                // It's taken so that looping and seeking back to the start later really go to the beginning
+                let start_time = start_time.take();
+                let time = match time {
+                    Some(time) if !time.is_zero() => Some(time),
+                    _ => start_time
+                };
+                return match time {
+                    Some(time) => ytdl_from(&source_url, time).await,
+                    None => ytdl(source_url).await
+                };
             },
-            LazyQueued::Lazier(ref search_query) => {
+            LazyQueued::Lazier(ref search_query) | LazyQueued::Search(ref search_query) => {
                 return ytdl_search(search_query).await;
+            },
+            LazyQueued::Matched(ref track_data, ref matched) => {
+                return match matched.get(track_data).await {
+                    Some(candidate) => ytdl(candidate.url).await,
+                    None => ytdl_search(track_data.query()).await
+                };
+            },
+            LazyQueued::Direct(ref metadata) => {
+                let source_url = metadata.source_url.clone().unwrap();
+                return match time {
+                    // Live streams have no duration and can't be seeked, they simply reconnect
+                    Some(time) if !time.is_zero() && metadata.duration.is_some() => {
+                        let timestamp = format!("{:.3}", time.as_secs_f64());
+                        ffmpeg_optioned(source_url, &["-ss", &timestamp], &FFMPEG_ARGS).await
+                    },
+                    _ => ffmpeg(source_url).await
+                };
             }
         }
     }
 
     async fn lazy_init(&mut self) -> songbird::input::error::Result<(Option<Metadata>, Codec, Container)> {
         match *self {
-            LazyQueued::Lazy(ref metadata) => {
-                return Ok((Some(metadata.clone()), Codec::FloatPcm, Container::Raw));
+            LazyQueued::Lazy(ref metadata, start_time) => {
+                let mut metadata = metadata.clone();
+                metadata.start_time = start_time;
+                return Ok((Some(metadata), Codec::FloatPcm, Container::Raw));
             },
             LazyQueued::Lazier(ref search_query) => {
-                let mut metadata = Metadata::default();
+                return Ok((Some(lazy_metadata(search_query.clone())), Codec::FloatPcm, Container::Raw));
+            },
+            LazyQueued::Search(ref search_query) => {
+                let mut metadata = ytdl_search_metadata(search_query).await.map_err(|_| InputError::Metadata)?;
                 metadata.channels = Some(2);
                 metadata.sample_rate = Some(48000);
-                metadata.title = Some(search_query.clone());
-                metadata.track = Some("$lazy_metadata$".to_owned());
                 return Ok((Some(metadata), Codec::FloatPcm, Container::Raw));
+            },
+            LazyQueued::Matched(ref track_data, _) => {
+                return Ok((Some(lazy_metadata(track_data.query())), Codec::FloatPcm, Container::Raw));
+            },
+            LazyQueued::Direct(ref metadata) => {
+                return Ok((Some(metadata.clone()), Codec::FloatPcm, Container::Raw));
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::QueryError;
+
+    #[test]
+    fn tells_links_from_plain_text() {
+        for query in ["never gonna give you up", "rick", "artist:song", "local:albums/Some Album", "spotify:liked", ""] {
+            assert!(parse_url(query).is_none(), "{}", query);
+        }
+
+        let cases = vec![
+            ("youtu.be/dQw4w9WgXcQ", "https://youtu.be/dQw4w9WgXcQ"),
+            ("https://soundcloud.com/artist/track", "https://soundcloud.com/artist/track"),
+            ("http://127.0.0.1:8000/track.mp3", "http://127.0.0.1:8000/track.mp3"),
+            ("http://[::1]/stream", "http://[::1]/stream"),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(parse_url(query).unwrap().unwrap().as_str(), expected, "{}", query);
+        }
+        assert_eq!(url_host("https://Open.Spotify.com/track/1").as_deref(), Some("open.spotify.com"));
+        assert_eq!(url_host("some song"), None);
+    }
+
+    #[test]
+    fn returns_typed_errors() {
+        let error = parse_url("ftp://example.org/track.mp3").unwrap().unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::UnsupportedScheme {..})));
+
+        let error = parse_url("http://example.org:99999/").unwrap().unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::MalformedUrl {..})));
+    }
+}
@@ -48,6 +48,109 @@ macro_rules! url_error {
     }
 }
 
+#[derive(Debug, Clone)]
+pub enum QueryError {
+    MalformedUrl {description: String, line: u32, file: String},
+    UnsupportedScheme {scheme: String, line: u32, file: String},
+    UnrecognizedYouTubeUrl {url: String, line: u32, file: String},
+    InvalidVideoId {id: String, line: u32, file: String},
+    UnknownSpotifyContentType {content_type: String, line: u32, file: String},
+    MissingSpotifyId {line: u32, file: String},
+    SpotifyPodcast {line: u32, file: String},
+    SpotifyNotLinked {line: u32, file: String},
+    UnsupportedUrl {url: String, line: u32, file: String},
+    NoSearchResults {query: String, line: u32, file: String},
+    LocalLibraryDisabled {line: u32, file: String},
+    InvalidLocalPath {path: String, line: u32, file: String},
+    LocalFileNotFound {path: String, line: u32, file: String},
+    NoLocalAudio {path: String, line: u32, file: String},
+    PrivateHost {host: String, line: u32, file: String}
+}
+
+impl QueryError {
+    /// Explanation meant for the user who sent the query
+    pub fn user_description(&self) -> String {
+        match *self {
+            Self::MalformedUrl {..} => "That Link Couldn't Be Read".to_owned(),
+            Self::UnsupportedScheme {ref scheme, ..} => format!("`{}` Links Aren't Supported", scheme),
+            Self::UnrecognizedYouTubeUrl {..} => "That YouTube Link Doesn't Point To a Video Or Playlist".to_owned(),
+            Self::InvalidVideoId {ref id, ..} => format!("`{}` Isn't a Valid YouTube Video Id", id),
+            Self::UnknownSpotifyContentType {ref content_type, ..} => format!("Spotify `{}` Links Aren't Supported", content_type),
+            Self::MissingSpotifyId {..} => "That Spotify Link Is Missing an Id".to_owned(),
+            Self::SpotifyPodcast {..} => "Podcasts Aren't Supported".to_owned(),
+            Self::SpotifyNotLinked {..} => "Link Your Spotify Account With `/spotify link` First".to_owned(),
+            Self::UnsupportedUrl {..} => "Nothing Playable Was Found At That Link".to_owned(),
+            Self::NoSearchResults {ref query, ..} => format!("Nothing Was Found For `{}`", query),
+            Self::LocalLibraryDisabled {..} => "There's No Local Music Library Set Up".to_owned(),
+            Self::InvalidLocalPath {ref path, ..} => format!("`{}` Is Outside The Music Library", path),
+            Self::LocalFileNotFound {ref path, ..} => format!("`{}` Doesn't Exist In The Music Library", path),
+            Self::NoLocalAudio {ref path, ..} => format!("There's No Audio In `{}`", path),
+            Self::PrivateHost {..} => "Links To Private Addresses Aren't Allowed".to_owned()
+        }
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match *self {
+            Self::MalformedUrl {ref description, ref line, ref file} => write!(f, "{}:{} Malformed Url: `{}`", file, line, description),
+            Self::UnsupportedScheme {ref scheme, ref line, ref file} => write!(f, "{}:{} Unsupported Scheme: `{}`", file, line, scheme),
+            Self::UnrecognizedYouTubeUrl {ref url, ref line, ref file} => write!(f, "{}:{} Unrecognized YouTube Url: `{}`", file, line, url),
+            Self::InvalidVideoId {ref id, ref line, ref file} => write!(f, "{}:{} Invalid Video Id: `{}`", file, line, id),
+            Self::UnknownSpotifyContentType {ref content_type, ref line, ref file} => write!(f, "{}:{} Unknown Spotify Content Type: `{}`", file, line, content_type),
+            Self::MissingSpotifyId {ref line, ref file} => write!(f, "{}:{} Missing Spotify Id", file, line),
+            Self::SpotifyPodcast {ref line, ref file} => write!(f, "{}:{} Spotify Podcast", file, line),
+            Self::SpotifyNotLinked {ref line, ref file} => write!(f, "{}:{} Spotify Not Linked", file, line),
+            Self::UnsupportedUrl {ref url, ref line, ref file} => write!(f, "{}:{} Unsupported Url: `{}`", file, line, url),
+            Self::NoSearchResults {ref query, ref line, ref file} => write!(f, "{}:{} No Search Results: `{}`", file, line, query),
+            Self::LocalLibraryDisabled {ref line, ref file} => write!(f, "{}:{} Local Library Disabled", file, line),
+            Self::InvalidLocalPath {ref path, ref line, ref file} => write!(f, "{}:{} Invalid Local Path: `{}`", file, line, path),
+            Self::LocalFileNotFound {ref path, ref line, ref file} => write!(f, "{}:{} Local File Not Found: `{}`", file, line, path),
+            Self::NoLocalAudio {ref path, ref line, ref file} => write!(f, "{}:{} No Local Audio: `{}`", file, line, path),
+            Self::PrivateHost {ref host, ref line, ref file} => write!(f, "{}:{} Private Host: `{}`", file, line, host)
+        };
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[macro_export] 
+macro_rules! query_error {
+    ($variant: ident $(, $field: ident: $value: expr)*) => {
+        $crate::error::QueryError::$variant {$($field: $value.to_owned(),)* line: line!(), file: file!().into()}
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AccountError {
+    MissingEnv {name: String, line: u32, file: String},
+    InvalidRedirectUri {uri: String, line: u32, file: String},
+    LinkExpired {line: u32, file: String},
+    AuthorizationDenied {reason: String, line: u32, file: String},
+    MissingRefreshToken {line: u32, file: String}
+}
+
+impl Display for AccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match *self {
+            Self::MissingEnv {ref name, ref line, ref file} => write!(f, "{}:{} Missing Env: `{}`", file, line, name),
+            Self::InvalidRedirectUri {ref uri, ref line, ref file} => write!(f, "{}:{} Invalid Redirect Uri: `{}`", file, line, uri),
+            Self::LinkExpired {ref line, ref file} => write!(f, "{}:{} Link Expired", file, line),
+            Self::AuthorizationDenied {ref reason, ref line, ref file} => write!(f, "{}:{} Authorization Denied: `{}`", file, line, reason),
+            Self::MissingRefreshToken {ref line, ref file} => write!(f, "{}:{} Missing Refresh Token", file, line)
+        };
+    }
+}
+
+impl std::error::Error for AccountError {}
+
+#[macro_export] 
+macro_rules! account_error {
+    ($variant: ident $(, $field: ident: $value: expr)*) => {
+        crate::error::AccountError::$variant {$($field: $value.to_owned(),)* line: line!(), file: file!().into()}
+    }
+}
+
 // TODO: Complete this error
 #[derive(Debug)]
 pub enum VoiceError {
@@ -75,4 +178,62 @@ macro_rules! different_channel {
     () => {
         crate::error::VoiceError::DifferentChannel {line: line!(), file: file!().into()}
     }
-}
\ No newline at end of file
+}
+#[derive(Debug, Clone)]
+pub enum QuotaError {
+    BudgetReached {used: u32, threshold: u32, line: u32, file: String}
+}
+
+impl Display for QuotaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match *self {
+            Self::BudgetReached {ref used, ref threshold, ref line, ref file} => write!(f, "{}:{} Budget Reached: `{}/{}` units", file, line, used, threshold)
+        };
+    }
+}
+
+impl std::error::Error for QuotaError {}
+
+#[macro_export] 
+macro_rules! quota_error {
+    ($variant: ident $(, $field: ident: $value: expr)*) => {
+        crate::error::QuotaError::$variant {$($field: $value.to_owned(),)* line: line!(), file: file!().into()}
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum QueueError {
+    InvalidPosition {position: usize, len: usize, line: u32, file: String},
+    InvalidRange {range: String, line: u32, file: String},
+    EmptyQueue {line: u32, file: String}
+}
+
+impl QueueError {
+    /// Explanation meant for the user who edited the queue
+    pub fn user_description(&self) -> String {
+        match *self {
+            Self::InvalidPosition {ref position, ref len, ..} => format!("There's No Track {} In The Queue, Pick One From 1 To {}", position, len - 1),
+            Self::InvalidRange {ref range, ..} => format!("`{}` Isn't a Track Number Or a Range Like `3-7`", range),
+            Self::EmptyQueue {..} => "Nothing Is Queued Up".to_owned()
+        }
+    }
+}
+
+impl Display for QueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match *self {
+            Self::InvalidPosition {ref position, ref len, ref line, ref file} => write!(f, "{}:{} Invalid Position: `{}` of `{}`", file, line, position, len),
+            Self::InvalidRange {ref range, ref line, ref file} => write!(f, "{}:{} Invalid Range: `{}`", file, line, range),
+            Self::EmptyQueue {ref line, ref file} => write!(f, "{}:{} Empty Queue", file, line)
+        };
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+#[macro_export] 
+macro_rules! queue_error {
+    ($variant: ident $(, $field: ident: $value: expr)*) => {
+        crate::error::QueueError::$variant {$($field: $value.to_owned(),)* line: line!(), file: file!().into()}
+    }
+}
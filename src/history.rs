@@ -0,0 +1,128 @@
+use std::{ collections::{ HashMap, HashSet, VecDeque }, sync::Arc, time::{ Duration, SystemTime } };
+use poise::async_trait;
+use songbird::{ EventContext, tracks::TrackHandle };
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use crate::{ AddedBy, LazyMetadataTrait, MiniMetadata, MiniUser, TrackSourceTrait, sources::QueuedItem };
+
+const MAX_HISTORY: usize = 50;
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub metadata: MiniMetadata,
+    pub added_by: Option<MiniUser>,
+    pub played_at: SystemTime,
+    /// What `/previous` queues the track again from
+    pub source: Option<QueuedItem>
+}
+
+/// The tracks each guild played last, newest first
+#[derive(Default)]
+pub struct PlaybackHistory {
+    entries: Mutex<HashMap<u64, VecDeque<HistoryEntry>>>,
+    ignored: Mutex<HashSet<Uuid>>
+}
+
+impl PlaybackHistory {
+    pub async fn record(&self, guild_id: u64, entry: HistoryEntry) {
+        let mut entries = self.entries.lock().await;
+        let guild_entries = entries.entry(guild_id).or_default();
+        guild_entries.push_front(entry);
+        guild_entries.truncate(MAX_HISTORY);
+    }
+
+    pub async fn entries(&self, guild_id: u64) -> Vec<HistoryEntry> {
+        self.entries.lock().await.get(&guild_id).map_or(vec![], |guild_entries| guild_entries.iter().cloned().collect())
+    }
+
+    /// Removes the newest entry, so going back again reaches the one before it
+    pub async fn take_latest(&self, guild_id: u64) -> Option<HistoryEntry> {
+        self.entries.lock().await.get_mut(&guild_id).and_then(|guild_entries| guild_entries.pop_front())
+    }
+
+    /// Keeps the track out of the history when it ends, for tracks `/previous` skips over
+    pub async fn ignore(&self, track: Uuid) {
+        self.ignored.lock().await.insert(track);
+    }
+
+    async fn is_ignored(&self, track: Uuid) -> bool {
+        self.ignored.lock().await.remove(&track)
+    }
+}
+
+/// Records tracks as they end. Tracks stopped before they played, like removed ones, were never heard and are left out
+pub struct HistoryEventHandler {
+    pub guild_id: u64,
+    pub history: Arc<PlaybackHistory>
+}
+
+impl HistoryEventHandler {
+    async fn entry(track: &TrackHandle) -> HistoryEntry {
+        let metadata = match track.read_lazy_metadata().await {
+            Some(metadata) => metadata,
+            None if track.is_lazy() => MiniMetadata { title: track.metadata().title.clone().unwrap_or_default(), ..MiniMetadata::empty() },
+            None => MiniMetadata::lossy_from_metadata(track.metadata().clone())
+        };
+        HistoryEntry { metadata, added_by: track.read_added_by().await, played_at: SystemTime::now(), source: track.read_track_source().await }
+    }
+}
+
+#[async_trait]
+impl songbird::events::EventHandler for HistoryEventHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<songbird::Event> {
+        if let EventContext::Track(slice) = ctx {
+            for (track_state, track) in slice.iter() {
+                if track_state.play_time == Duration::ZERO || self.history.is_ignored(track.uuid()).await {
+                    continue;
+                }
+                self.history.record(self.guild_id, Self::entry(track).await).await;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str) -> HistoryEntry {
+        HistoryEntry { metadata: MiniMetadata { title: title.to_owned(), ..MiniMetadata::empty() }, added_by: None, played_at: SystemTime::now(), source: None }
+    }
+
+    fn titles(entries: Vec<HistoryEntry>) -> Vec<String> {
+        entries.into_iter().map(|entry| entry.metadata.title).collect()
+    }
+
+    #[tokio::test]
+    async fn keeps_the_newest_tracks_per_guild() {
+        let history = PlaybackHistory::default();
+        for index in 0..MAX_HISTORY + 5 {
+            history.record(1, entry(&index.to_string())).await;
+        }
+        history.record(2, entry("other guild")).await;
+
+        let entries = history.entries(1).await;
+        assert_eq!(entries.len(), MAX_HISTORY);
+        assert_eq!(entries[0].metadata.title, (MAX_HISTORY + 4).to_string());
+        assert_eq!(entries[MAX_HISTORY - 1].metadata.title, "5");
+        assert_eq!(titles(history.entries(2).await), vec!["other guild"]);
+        assert!(history.entries(3).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn going_back_walks_further_into_the_history() {
+        let history = PlaybackHistory::default();
+        history.record(1, entry("first")).await;
+        history.record(1, entry("second")).await;
+
+        assert_eq!(history.take_latest(1).await.map(|entry| entry.metadata.title), Some("second".to_owned()));
+        assert_eq!(history.take_latest(1).await.map(|entry| entry.metadata.title), Some("first".to_owned()));
+        assert!(history.take_latest(1).await.is_none());
+
+        let track = Uuid::from_u128(1);
+        history.ignore(track).await;
+        assert!(history.is_ignored(track).await);
+        assert!(!history.is_ignored(track).await);
+    }
+}
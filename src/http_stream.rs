@@ -0,0 +1,422 @@
+use std::{ time::{ Duration, Instant }, sync::Arc, net::IpAddr };
+use reqwest::{ Client, Response, header::CONTENT_TYPE, redirect::Policy };
+use songbird::{ input::Metadata, tracks::{ TrackHandle, PlayMode } };
+use poise::serenity_prelude::{ ChannelId, Http };
+use tokio::{ net::lookup_host, time::{ sleep, timeout } };
+use url::{ Host, Url };
+use crate::{
+    query_error,
+    error::{ Error, QueryError },
+    utils::create_now_playing_embed,
+    convert_query::ffprobe_metadata,
+    AddedBy, MiniMetadata
+};
+
+pub static AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "ogg", "oga", "opus", "flac", "wav", "m4a", "aac"];
+const MAX_REDIRECTS: usize = 10;
+/// How soon the stream title connection closes after its track ends
+const TRACK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ContentKind {
+    Audio,
+    M3u,
+    Pls,
+    Other
+}
+
+/// An audio file or radio stream that ffmpeg can play without going through yt-dlp
+#[derive(Debug, Clone)]
+pub struct HttpAudio {
+    pub url: String,
+    pub metadata: Metadata,
+    pub is_live: bool
+}
+
+/// Returns `None` for links that aren't audio, those are left to yt-dlp.
+/// The returned url is the one checked after redirects, though ffmpeg still follows any further redirects and resolves the host again on its own
+pub async fn probe(url: &str) -> Result<Option<HttpAudio>, Error> {
+    probe_with(url, true).await
+}
+
+/// Links to the bot's own machine or network are refused unless `check_hosts` is off, which only tests do
+async fn probe_with(url: &str, check_hosts: bool) -> Result<Option<HttpAudio>, Error> {
+    let client = Client::builder().redirect(redirect_policy(check_hosts)).build()?;
+    let mut url = Url::parse(url)?;
+    if check_hosts {
+        check_public_host(&url).await?;
+    }
+
+    let mut response = fetch_headers(&client, &url).await?;
+    url = response.url().clone();
+    match content_kind(&response, &url) {
+        kind @ (ContentKind::M3u | ContentKind::Pls) => {
+            let body = client.get(url.clone()).send().await?.text().await?;
+            let entries = parse_playlist(&body, kind, &url);
+            // Radio playlists list mirrors of the same stream, so the first entry is enough
+            url = match entries.into_iter().next() {
+                Some(entry) => entry,
+                None => return Ok(None)
+            };
+            if check_hosts {
+                check_public_host(&url).await?;
+            }
+            response = fetch_headers(&client, &url).await?;
+            url = response.url().clone();
+            if content_kind(&response, &url) != ContentKind::Audio {
+                return Ok(None);
+            }
+        },
+        ContentKind::Audio => (),
+        ContentKind::Other => return Ok(None)
+    }
+
+    let headers = response.headers();
+    let station_name = headers.get("icy-name").and_then(|value| value.to_str().ok()).map(|value| value.to_owned());
+    let is_icy = station_name.is_some() || headers.contains_key("icy-br");
+
+    // Plenty of plain files are sent chunked, so only ICY headers or a missing duration mean it's live
+    let mut metadata = if is_icy { Metadata::default() } else { ffprobe_metadata(url.as_str()).await.unwrap_or_default() };
+    let is_live = is_icy || metadata.duration.is_none();
+    metadata.title = metadata.title.or(station_name).or_else(|| file_name(&url));
+    metadata.source_url = Some(url.to_string());
+    metadata.channels = Some(2);
+    metadata.sample_rate = Some(48000);
+
+    Ok(Some(HttpAudio { url: url.into(), metadata, is_live }))
+}
+
+/// Refuses hosts that are, or resolve to, loopback, private or link local addresses
+async fn check_public_host(url: &Url) -> Result<(), Error> {
+    let host = url.host_str().unwrap_or_default().to_owned();
+    let addresses: Vec<IpAddr> = match url.host() {
+        Some(Host::Ipv4(address)) => vec![address.into()],
+        Some(Host::Ipv6(address)) => vec![address.into()],
+        Some(Host::Domain(domain)) => lookup_host((domain, url.port_or_known_default().unwrap_or(80))).await?.map(|address| address.ip()).collect(),
+        None => vec![]
+    };
+    if addresses.is_empty() || addresses.iter().any(|address| !is_public_address(address)) {
+        return Err(query_error!(PrivateHost, host: host).into());
+    }
+    Ok(())
+}
+
+/// Redirects can't be resolved without blocking, so only those to addresses written out are refused
+fn redirect_policy(check_hosts: bool) -> Policy {
+    Policy::custom(move |attempt| {
+        let is_private = match attempt.url().host() {
+            Some(Host::Ipv4(address)) => !is_public_address(&address.into()),
+            Some(Host::Ipv6(address)) => !is_public_address(&address.into()),
+            Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+            None => true
+        };
+        if check_hosts && is_private {
+            let host = attempt.url().host_str().unwrap_or_default().to_owned();
+            attempt.error(query_error!(PrivateHost, host: host))
+        } else if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+/// Whether the probe refused the link for its host, also when the refusal came from a redirect inside reqwest
+pub fn is_private_host(error: &Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error.as_ref());
+    while let Some(error) = source {
+        if matches!(error.downcast_ref::<QueryError>(), Some(QueryError::PrivateHost {..})) {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+pub fn is_public_address(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => !(address.is_private() || address.is_loopback() || address.is_link_local() || address.is_unspecified() || address.is_broadcast()
+            // Carrier grade NAT, 100.64.0.0/10
+            || (address.octets()[0] == 100 && address.octets()[1] & 0xc0 == 64)),
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(mapped) => is_public_address(&mapped.into()),
+            // Unique local fc00::/7 and link local fe80::/10
+            None => !(address.is_loopback() || address.is_unspecified() || address.segments()[0] & 0xfe00 == 0xfc00 || address.segments()[0] & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+async fn fetch_headers(client: &Client, url: &Url) -> Result<Response, Error> {
+    if let Ok(response) = client.head(url.clone()).send().await {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+    }
+    // Some stream servers don't answer HEAD, the body is dropped along with the response
+    Ok(client.get(url.clone()).send().await?.error_for_status()?)
+}
+
+fn content_kind(response: &Response, url: &Url) -> ContentKind {
+    let content_type = response.headers().get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or("").trim().to_ascii_lowercase());
+
+    match content_type.as_deref() {
+        Some("audio/x-mpegurl" | "audio/mpegurl") => ContentKind::M3u,
+        Some("audio/x-scpls") => ContentKind::Pls,
+        Some(content_type) if content_type.starts_with("audio/") || content_type == "application/ogg" => ContentKind::Audio,
+        Some("text/html") => ContentKind::Other,
+        _ => content_kind_from_extension(url)
+    }
+}
+
+fn content_kind_from_extension(url: &Url) -> ContentKind {
+    let extension = url.path().rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("m3u") => ContentKind::M3u,
+        Some("pls") => ContentKind::Pls,
+        Some(extension) if AUDIO_EXTENSIONS.contains(&extension) => ContentKind::Audio,
+        _ => ContentKind::Other
+    }
+}
+
+fn parse_playlist(body: &str, kind: ContentKind, base_url: &Url) -> Vec<Url> {
+    match kind {
+        ContentKind::Pls => parse_pls(body, base_url),
+        _ => parse_m3u(body, base_url)
+    }
+}
+
+pub fn parse_m3u(body: &str, base_url: &Url) -> Vec<Url> {
+    body.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| base_url.join(line).ok())
+        .collect()
+}
+
+pub fn parse_pls(body: &str, base_url: &Url) -> Vec<Url> {
+    body.lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter(|(key, _)| key.to_ascii_lowercase().starts_with("file"))
+        .filter_map(|(_, value)| base_url.join(value.trim()).ok())
+        .collect()
+}
+
+fn file_name(url: &Url) -> Option<String> {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_owned())
+}
+
+/// Extracts the title from an ICY metadata block, e.g. `StreamTitle='Artist - Title';StreamUrl='';`
+pub fn parse_stream_title(block: &[u8]) -> Option<String> {
+    let block = String::from_utf8_lossy(block);
+    let start = block.find("StreamTitle='")? + "StreamTitle='".len();
+    let end = block[start..].find("';").map(|end| start + end).unwrap_or(block.trim_end_matches('\0').len());
+    let title = block[start..end].trim();
+    if title.is_empty() { None } else { Some(title.to_owned()) }
+}
+
+/// Announces the song currently playing on a radio stream, closing the connection once the track ends
+pub async fn follow_stream_title(url: String, handle: TrackHandle, channel_id: ChannelId, http: Arc<Http>) {
+    loop {
+        match handle.get_info().await {
+            Ok(info) if info.playing == PlayMode::Play => break,
+            Ok(info) if !info.playing.is_done() => sleep(TRACK_CHECK_INTERVAL).await,
+            _ => return
+        }
+    }
+
+    let response = match Client::new().get(&url).header("Icy-MetaData", "1").send().await {
+        Ok(response) => response,
+        Err(_) => return
+    };
+    let metaint = match response.headers().get("icy-metaint").and_then(|value| value.to_str().ok()).and_then(|value| value.parse::<usize>().ok()) {
+        Some(metaint) => metaint,
+        None => return
+    };
+
+    let mut reader = IcyReader::new(metaint);
+    let mut last_title: Option<String> = None;
+    let mut response = response;
+    let mut last_check = Instant::now();
+    loop {
+        // Returning drops the response, which closes the second connection to the stream
+        if last_check.elapsed() >= TRACK_CHECK_INTERVAL {
+            if has_ended(&handle).await {
+                return;
+            }
+            last_check = Instant::now();
+        }
+        let chunk = match timeout(TRACK_CHECK_INTERVAL, response.chunk()).await {
+            Ok(Ok(Some(chunk))) => chunk,
+            Ok(_) => return,
+            Err(_) => continue
+        };
+        for block in reader.feed(&chunk) {
+            if let Some(title) = parse_stream_title(&block) {
+                if last_title.as_ref() != Some(&title) {
+                    let metadata = MiniMetadata { title: title.clone(), source_url: url.clone(), ..MiniMetadata::live() };
+                    let added_by = handle.read_added_by().await;
+                    let _ = channel_id.send_message(&http, |message| message.set_embed(create_now_playing_embed(metadata, added_by))).await;
+                    last_title = Some(title);
+                }
+            }
+        }
+    }
+}
+
+async fn has_ended(handle: &TrackHandle) -> bool {
+    handle.get_info().await.map_or(true, |info| info.playing.is_done())
+}
+
+/// Splits an ICY stream into its metadata blocks, skipping the audio in between
+struct IcyReader {
+    metaint: usize,
+    audio_left: usize,
+    block_len: Option<usize>,
+    block: Vec<u8>
+}
+
+impl IcyReader {
+    fn new(metaint: usize) -> Self {
+        Self { metaint, audio_left: metaint, block_len: None, block: vec![] }
+    }
+
+    fn feed(&mut self, mut chunk: &[u8]) -> Vec<Vec<u8>> {
+        let mut blocks = vec![];
+        while !chunk.is_empty() {
+            if self.audio_left > 0 {
+                let skipped = self.audio_left.min(chunk.len());
+                self.audio_left -= skipped;
+                chunk = &chunk[skipped..];
+                continue;
+            }
+            let block_len = match self.block_len {
+                Some(block_len) => block_len,
+                None => {
+                    let block_len = chunk[0] as usize * 16;
+                    chunk = &chunk[1..];
+                    self.block_len = Some(block_len);
+                    block_len
+                }
+            };
+            let taken = (block_len - self.block.len()).min(chunk.len());
+            self.block.extend_from_slice(&chunk[..taken]);
+            chunk = &chunk[taken..];
+            if self.block.len() == block_len {
+                if block_len > 0 {
+                    blocks.push(std::mem::take(&mut self.block));
+                }
+                self.block_len = None;
+                self.audio_left = self.metaint;
+            }
+        }
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{ net::TcpListener, io::{ AsyncReadExt, AsyncWriteExt } };
+
+    #[test]
+    fn parses_playlists() {
+        let base_url = Url::parse("http://radio.example.org/listen/station.m3u").unwrap();
+
+        let m3u = "#EXTM3U\n#EXTINF:-1,Station\nhttp://stream.example.org:8000/live\n\nrelative.mp3\n";
+        assert_eq!(parse_m3u(m3u, &base_url), vec![
+            Url::parse("http://stream.example.org:8000/live").unwrap(),
+            Url::parse("http://radio.example.org/listen/relative.mp3").unwrap()
+        ]);
+
+        let pls = "[playlist]\nNumberOfEntries=2\nFile1=http://stream.example.org:8000/live\nTitle1=Station\nFile2=http://backup.example.org/live\nVersion=2\n";
+        assert_eq!(parse_pls(pls, &base_url), vec![
+            Url::parse("http://stream.example.org:8000/live").unwrap(),
+            Url::parse("http://backup.example.org/live").unwrap()
+        ]);
+    }
+
+    #[test]
+    fn reads_icy_metadata() {
+        assert_eq!(parse_stream_title(b"StreamTitle='Artist - Title';StreamUrl='';\0\0"), Some("Artist - Title".to_owned()));
+        assert_eq!(parse_stream_title(b"StreamTitle='';\0\0\0"), None);
+
+        let title = b"StreamTitle='Song';";
+        let mut block = title.to_vec();
+        block.resize(32, 0);
+        let mut stream = vec![0u8; 4];
+        stream.push(2);
+        stream.extend_from_slice(&block);
+        stream.extend_from_slice(&[0u8; 4]);
+        stream.push(0);
+
+        let mut reader = IcyReader::new(4);
+        let mut blocks = vec![];
+        // Feeding byte by byte checks that blocks split across chunks are reassembled
+        for byte in stream.chunks(1) {
+            blocks.extend(reader.feed(byte));
+        }
+        assert_eq!(blocks, vec![block]);
+    }
+
+    async fn serve(responses: Vec<(&'static str, String)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = vec![0u8; 4096];
+                let len = socket.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..len]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_owned();
+                if let Some((_, response)) = responses.iter().find(|(response_path, _)| *response_path == path) {
+                    let _ = socket.write_all(response.as_bytes()).await;
+                }
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn probes_radio_streams_behind_playlists() {
+        let stream_headers = "HTTP/1.1 200 OK\r\nContent-Type: audio/mpeg\r\nicy-name: Test Radio\r\nConnection: close\r\n\r\n".to_owned();
+        let pls = "[playlist]\nFile1=/live\nVersion=2";
+        let address = serve(vec![
+            ("/live", stream_headers),
+            ("/station.pls", format!("HTTP/1.1 200 OK\r\nContent-Type: audio/x-scpls\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", pls.len(), pls)),
+            ("/page", "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()),
+            ("/moved", "HTTP/1.1 302 Found\r\nLocation: /live\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned())
+        ]).await;
+
+        let audio = probe_with(&format!("{}/station.pls", address), false).await.unwrap().unwrap();
+        assert!(audio.is_live);
+        assert_eq!(audio.url, format!("{}/live", address));
+        assert_eq!(audio.metadata.title, Some("Test Radio".to_owned()));
+        assert_eq!(audio.metadata.duration, None);
+
+        assert!(probe_with(&format!("{}/page", address), false).await.unwrap().is_none());
+
+        // ffmpeg is handed the link the redirects ended at
+        let audio = probe_with(&format!("{}/moved", address), false).await.unwrap().unwrap();
+        assert_eq!(audio.url, format!("{}/live", address));
+
+        let error = probe(&format!("{}/live", address)).await.unwrap_err();
+        assert!(is_private_host(&error));
+    }
+
+    #[test]
+    fn tells_public_addresses_apart() {
+        let public = ["93.184.216.34", "100.128.0.1", "2606:2800:220:1::1"];
+        let private = ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"];
+
+        for address in public {
+            assert!(is_public_address(&address.parse().unwrap()), "{}", address);
+        }
+        for address in private {
+            assert!(!is_public_address(&address.parse().unwrap()), "{}", address);
+        }
+    }
+}
@@ -4,19 +4,35 @@ pub mod error;
 pub mod convert_query;
 pub mod youtube_scraper;
 pub mod utils;
+pub mod http_stream;
+pub mod local_library;
+pub mod track_matcher;
+pub mod spotify_accounts;
+pub mod sources;
+pub mod retry;
+pub mod youtube_quota;
+pub mod query_autocomplete;
+pub mod queue_edit;
+pub mod loop_mode;
+pub mod history;
+pub mod playlist_import;
 
 use std::{ time::Duration, sync::Arc };
 use tokio::sync::Mutex;
 use serenity::model::channel::Message;
 use poise::{ reply::ReplyHandle, async_trait, serenity_prelude::{ChannelId, Http, Color, CreateEmbed, User} };
-use google_youtube3::{ YouTube, hyper::client::HttpConnector, hyper_rustls::HttpsConnector };
-use rspotify::ClientCredsSpotify;
 use songbird::{ input::Metadata, tracks::TrackHandle, Call, EventContext };
-use spotify_to_query::{ TrackData, extract_album_queries, extract_playlist_queries, extract_track_query };
-use youtube_api::{ extract_playlist_video_metadata, extract_video_metadata };
+use spotify_to_query::TrackData;
 use error::{ Error, LibError };
 use youtube_scraper::search;
 use utils::{create_now_playing_embed, format_duration};
+use convert_query::{ LazyQueued, QueuedInput, QueryOptions };
+use spotify_accounts::SpotifyAccounts;
+use sources::{ QueuedItem, SourceRegistry };
+use youtube_quota::QuotaMeter;
+use query_autocomplete::QueryAutocomplete;
+use loop_mode::LoopModes;
+use history::PlaybackHistory;
 
 #[derive(Debug)]
 pub struct GeneralError {
@@ -56,13 +72,17 @@ impl CleanupObject {
 
 pub struct Data {
     pub cleanup: Mutex<Vec<CleanupObject>>,
-    pub youtube_client: YouTube<HttpsConnector<HttpConnector>>,
-    pub spotify_client: ClientCredsSpotify
+    pub sources: SourceRegistry,
+    pub spotify_accounts: Arc<SpotifyAccounts>,
+    pub youtube_quota: Arc<QuotaMeter>,
+    pub autocomplete: QueryAutocomplete,
+    pub loop_modes: Arc<LoopModes>,
+    pub history: Arc<PlaybackHistory>
 }
 
 impl Data {
-    pub fn new(youtube_client: YouTube<HttpsConnector<HttpConnector>>, spotify_client: ClientCredsSpotify) -> Self {
-        Self { cleanup: Mutex::new(Vec::new()), youtube_client, spotify_client }
+    pub fn new(sources: SourceRegistry, spotify_accounts: Arc<SpotifyAccounts>, youtube_quota: Arc<QuotaMeter>) -> Self {
+        Self { cleanup: Mutex::new(Vec::new()), sources, spotify_accounts, youtube_quota, autocomplete: QueryAutocomplete::new(), loop_modes: Arc::new(LoopModes::default()), history: Arc::new(PlaybackHistory::default()) }
     }
 
     pub async fn delete_after_delay<'a>(&self, reply_handle: ReplyHandle<'a>, delay: Duration) {
@@ -72,28 +92,8 @@ impl Data {
         }
     }
 
-    pub async fn extract_youtube_video_metadata(&self, id: &str) -> Result<Metadata, Error> {
-        extract_video_metadata(&self.youtube_client, id).await
-    }
-
-    pub async fn extract_youtube_playlist_metadata(&self, id: &str) -> Result<Vec<Metadata>, Error> {
-        extract_playlist_video_metadata(&self.youtube_client, id).await
-    }
-
-    pub async fn extract_spotify_track_query(&self, id: &str) -> Result<TrackData, Error> {
-        extract_track_query(&self.spotify_client, id).await
-    }
-
-    pub async fn extract_spotify_playlist_queries(&self, id: &str) -> Result<Vec<TrackData>, Error> {
-        extract_playlist_queries(&self.spotify_client, id).await
-    }
-
-    pub async fn extract_spotify_album_queries(&self, id: &str) -> Result<Vec<TrackData>, Error> {
-        extract_album_queries(&self.spotify_client, id).await
-    }
-
-    pub async fn convert_query(&self, query: &str) -> Result<Vec<songbird::input::Input>, Error> {
-        convert_query::convert_query(&self, query).await
+    pub async fn convert_query(&self, query: &str, options: QueryOptions) -> Result<Vec<QueuedInput>, Error> {
+        convert_query::convert_query(&self, query, options).await
     }
 }
 
@@ -103,16 +103,24 @@ pub type Context<'a> = poise::Context<'a, Data, Error>;
 pub struct MiniMetadata {
     pub title: String,
     pub duration: Duration,
-    pub source_url: String
+    pub source_url: String,
+    pub disc_number: Option<i32>,
+    pub track_number: Option<u32>,
+    pub is_live: bool
 }
 
 impl MiniMetadata {
     pub fn empty() -> Self {
-        Self { title: String::new(), duration: Duration::ZERO, source_url: String::new() }
+        Self { title: String::new(), duration: Duration::ZERO, source_url: String::new(), disc_number: None, track_number: None, is_live: false }
     }
 
+    pub fn live() -> Self {
+        Self { is_live: true, ..Self::empty() }
+    }
+
+    /// A missing duration is how live streams are marked
     pub fn lossy_from_metadata(value: Metadata) -> Self {
-        Self { title: value.title.unwrap_or("".to_owned()), duration: value.duration.unwrap_or(Duration::ZERO), source_url: value.source_url.unwrap_or("".to_owned()) }
+        Self { title: value.title.unwrap_or("".to_owned()), is_live: value.duration.is_none(), duration: value.duration.unwrap_or(Duration::ZERO), source_url: value.source_url.unwrap_or("".to_owned()), disc_number: None, track_number: None }
     }
 }
 
@@ -123,7 +131,7 @@ impl songbird::typemap::TypeMapKey for MiniMetadata {
 impl TryFrom<Metadata> for MiniMetadata {
     type Error = LibError;
     fn try_from(value: Metadata) -> Result<Self, Self::Error> {
-        Ok(Self { title: value.title.ok_or(missing_value!("title"))?, duration: value.duration.ok_or(missing_value!("duration"))?, source_url: value.source_url.ok_or(missing_value!("source_url"))? })
+        Ok(Self { title: value.title.ok_or(missing_value!("title"))?, duration: value.duration.ok_or(missing_value!("duration"))?, source_url: value.source_url.ok_or(missing_value!("source_url"))?, disc_number: None, track_number: None, is_live: false })
     }
 }
 
@@ -153,12 +161,27 @@ impl LazyMetadataTrait for TrackHandle {
     async fn generate_lazy_metadata(&mut self) {
         if self.is_lazy() {
             if let Some(ref query) = self.metadata().title {
-                let (title, video_id, duration) = search(query).await;
-                let mut source_url = String::new();
-                if let Some(video_id) = video_id {
-                    source_url = format!("https://youtu.be/{}", video_id);
-                }
-                let metadata = MiniMetadata {title: title.unwrap_or(String::new()), duration: duration.unwrap_or(Duration::ZERO), source_url };
+                let track_data = self.read_track_data().await;
+                // Spotify tracks show the match their audio plays, which is made only once
+                let matched = match self.read_track_source().await.map(|source| source.lazy_queued) {
+                    Some(LazyQueued::Matched(track_data, matched)) => matched.get(&track_data).await,
+                    _ => None
+                };
+                let (title, source_url, duration) = match matched {
+                    Some(candidate) => (Some(candidate.title), candidate.url, candidate.duration),
+                    None => match search(query).await.ok().and_then(|results| results.into_iter().next()) {
+                        Some(result) => (Some(result.title.clone()), result.url(), result.duration),
+                        None => (None, String::new(), None)
+                    }
+                };
+                let metadata = MiniMetadata {
+                    title: title.unwrap_or(String::new()),
+                    duration: duration.unwrap_or(Duration::ZERO),
+                    source_url,
+                    disc_number: track_data.as_ref().and_then(|track_data| track_data.disc_number),
+                    track_number: track_data.as_ref().and_then(|track_data| track_data.track_number),
+                    is_live: false
+                };
                 self.write_lazy_metadata(metadata).await
             }
         }
@@ -225,4 +248,46 @@ impl AddedBy for TrackHandle {
     async fn write_added_by<'a>(&mut self, user: &'a User) {
         self.typemap().write().await.insert::<MiniUser>(user.into());
     }
-}
\ No newline at end of file
+}
+
+impl songbird::typemap::TypeMapKey for TrackData {
+    type Value = TrackData;
+}
+
+#[async_trait]
+pub trait TrackDataTrait {
+    async fn read_track_data(&self) -> Option<TrackData>;
+    async fn write_track_data(&mut self, track_data: TrackData);
+}
+
+#[async_trait]
+impl TrackDataTrait for TrackHandle {
+    async fn read_track_data(&self) -> Option<TrackData> {
+        self.typemap().read().await.get::<TrackData>().cloned()
+    }
+
+    async fn write_track_data(&mut self, track_data: TrackData) {
+        self.typemap().write().await.insert::<TrackData>(track_data);
+    }
+}
+
+impl songbird::typemap::TypeMapKey for QueuedItem {
+    type Value = QueuedItem;
+}
+
+#[async_trait]
+pub trait TrackSourceTrait {
+    async fn read_track_source(&self) -> Option<QueuedItem>;
+    async fn write_track_source(&mut self, source: QueuedItem);
+}
+
+#[async_trait]
+impl TrackSourceTrait for TrackHandle {
+    async fn read_track_source(&self) -> Option<QueuedItem> {
+        self.typemap().read().await.get::<QueuedItem>().cloned()
+    }
+
+    async fn write_track_source(&mut self, source: QueuedItem) {
+        self.typemap().write().await.insert::<QueuedItem>(source);
+    }
+}
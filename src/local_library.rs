@@ -0,0 +1,94 @@
+use std::{ path::{ Path, PathBuf, Component }, time::Duration };
+use songbird::input::Metadata;
+use crate::{
+    query_error,
+    error::Error,
+    convert_query::ffprobe_metadata,
+    http_stream::AUDIO_EXTENSIONS
+};
+
+/// Resolves a `local:` path inside the music directory, refusing anything that would leave it
+pub fn resolve_path(music_directory: &Path, query_path: &str) -> Result<PathBuf, Error> {
+    let query_path = query_path.trim();
+    let relative_path = Path::new(query_path);
+    if relative_path.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(query_error!(InvalidLocalPath, path: query_path).into());
+    }
+
+    let music_directory = music_directory.canonicalize()?;
+    let path = music_directory.join(relative_path).canonicalize()
+        .map_err(|_| query_error!(LocalFileNotFound, path: query_path))?;
+    // Symlinks can still point outside of the library
+    if !path.starts_with(&music_directory) {
+        return Err(query_error!(InvalidLocalPath, path: query_path).into());
+    }
+    Ok(path)
+}
+
+/// A single file, or every audio file directly inside a directory in name order
+pub fn audio_files(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !path.is_dir() {
+        return Ok(if is_audio_file(path) { vec![path.to_owned()] } else { vec![] });
+    }
+
+    let mut files: Vec<PathBuf> = vec![];
+    for entry in std::fs::read_dir(path)? {
+        let entry_path = entry?.path();
+        if entry_path.is_file() && is_audio_file(&entry_path) {
+            files.push(entry_path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.as_str()))
+}
+
+pub async fn extract_local_metadata(path: &Path) -> Result<Metadata, Error> {
+    let path_string = path.to_string_lossy().to_string();
+    let mut metadata = ffprobe_metadata(&path_string).await.unwrap_or_default();
+
+    let file_stem = path.file_stem().map(|file_stem| file_stem.to_string_lossy().to_string());
+    metadata.title = match (metadata.title.take(), metadata.artist.clone()) {
+        (Some(title), Some(artist)) => Some(format!("{} - {}", artist, title)),
+        (Some(title), None) => Some(title),
+        (None, _) => file_stem
+    };
+    metadata.duration = metadata.duration.or(Some(Duration::ZERO));
+    metadata.source_url = Some(path_string);
+    metadata.channels = Some(2);
+    metadata.sample_rate = Some(48000);
+
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::QueryError;
+
+    #[test]
+    fn stays_inside_the_music_directory() {
+        let music_directory = std::env::temp_dir().join(format!("diwa_library_{}", std::process::id()));
+        std::fs::create_dir_all(music_directory.join("album")).unwrap();
+        std::fs::write(music_directory.join("album/01 intro.mp3"), b"").unwrap();
+
+        let path = resolve_path(&music_directory, "album/01 intro.mp3").unwrap();
+        assert!(path.ends_with("album/01 intro.mp3"));
+        assert_eq!(audio_files(&resolve_path(&music_directory, "album").unwrap()).unwrap(), vec![path]);
+
+        for relative_path in ["../etc/passwd", "album/../../etc/passwd", "/etc/passwd"] {
+            let error = resolve_path(&music_directory, relative_path).unwrap_err();
+            assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::InvalidLocalPath {..})), "{}", relative_path);
+        }
+
+        let error = resolve_path(&music_directory, "missing.mp3").unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::LocalFileNotFound {..})));
+
+        std::fs::remove_dir_all(music_directory).unwrap();
+    }
+}
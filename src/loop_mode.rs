@@ -0,0 +1,132 @@
+use std::{ collections::HashMap, sync::Arc };
+use poise::{ async_trait, serenity_prelude::{ ChannelId, Http } };
+use songbird::{ Call, EventContext, tracks::{ create_player, PlayMode, TrackHandle } };
+use tokio::{ spawn, sync::Mutex };
+use crate::{
+    MiniMetadata, MiniUser, TrackSourceTrait,
+    error::Error,
+    http_stream::follow_stream_title,
+    spotify_to_query::TrackData
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, poise::ChoiceParameter)]
+pub enum LoopMode {
+    #[default]
+    #[name = "off"]
+    Off,
+    #[name = "track"]
+    Track,
+    #[name = "queue"]
+    Queue
+}
+
+impl LoopMode {
+    /// What `/loop` without a mode switches to
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Off => Self::Track,
+            Self::Track => Self::Queue,
+            Self::Queue => Self::Off
+        }
+    }
+}
+
+/// Kept per guild rather than on track handles, so it outlives the tracks it applies to
+#[derive(Default)]
+pub struct LoopModes {
+    modes: Mutex<HashMap<u64, LoopMode>>
+}
+
+impl LoopModes {
+    pub async fn get(&self, guild_id: u64) -> LoopMode {
+        self.modes.lock().await.get(&guild_id).copied().unwrap_or_default()
+    }
+
+    pub async fn set(&self, guild_id: u64, mode: LoopMode) {
+        self.modes.lock().await.insert(guild_id, mode);
+    }
+}
+
+/// Loops every track that starts in track mode, and queues finished tracks again in queue mode.
+/// Skipped and removed tracks are stopped rather than finished, so they leave the rotation
+pub struct LoopEventHandler {
+    pub guild_id: u64,
+    pub handler: Arc<Mutex<Call>>,
+    pub loop_modes: Arc<LoopModes>,
+    pub channel_id: ChannelId,
+    pub http: Arc<Http>
+}
+
+impl LoopEventHandler {
+    /// A finished handle can't play again, so a new one is made from the source it was queued from
+    async fn requeue(&self, track: &TrackHandle) -> Result<(), Error> {
+        let source = match track.read_track_source().await {
+            Some(source) => source,
+            None => return Ok(())
+        };
+        let stream_url = source.stream_url.clone();
+        let (new_track, mut new_handle) = create_player(source.clone().into_input().await?.input);
+
+        let (added_by, track_data, lazy_metadata) = {
+            let typemap = track.typemap().read().await;
+            (typemap.get::<MiniUser>().cloned(), typemap.get::<TrackData>().cloned(), typemap.get::<MiniMetadata>().cloned())
+        };
+        {
+            let mut typemap = new_handle.typemap().write().await;
+            if let Some(added_by) = added_by {
+                typemap.insert::<MiniUser>(added_by);
+            }
+            if let Some(track_data) = track_data {
+                typemap.insert::<TrackData>(track_data);
+            }
+            if let Some(lazy_metadata) = lazy_metadata {
+                typemap.insert::<MiniMetadata>(lazy_metadata);
+            }
+        }
+        new_handle.write_track_source(source).await;
+        if let Some(stream_url) = stream_url {
+            spawn(follow_stream_title(stream_url, new_handle.clone(), self.channel_id, self.http.clone()));
+        }
+
+        self.handler.lock().await.enqueue(new_track);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl songbird::events::EventHandler for LoopEventHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<songbird::Event> {
+        if let EventContext::Track(slice) = ctx {
+            if let Some((track_state, track)) = slice.first() {
+                match (self.loop_modes.get(self.guild_id).await, track_state.playing) {
+                    (LoopMode::Track, PlayMode::Play) => {
+                        let _ = track.enable_loop();
+                    },
+                    (LoopMode::Queue, PlayMode::End) => {
+                        if let Err(err) = self.requeue(track).await {
+                            println!("Couldn't queue a finished track again: {}", err);
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_a_mode_per_guild() {
+        let loop_modes = LoopModes::default();
+        loop_modes.set(1, LoopMode::Queue).await;
+        assert_eq!(loop_modes.get(1).await, LoopMode::Queue);
+        assert_eq!(loop_modes.get(2).await, LoopMode::Off);
+
+        assert_eq!(LoopMode::Off.next().next().next(), LoopMode::Off);
+        assert_eq!(LoopMode::Off.next(), LoopMode::Track);
+    }
+}
@@ -1,40 +1,57 @@
 mod commands;
-mod convert_query;
-mod spotify_to_query;
-mod error;
-mod youtube_api;
 
 use std::env;
 use dotenv::dotenv;
-use diwa_rs::{Data, Context};
+use diwa_rs::{
+    Data, Context,
+    spotify_to_query::auth,
+    spotify_accounts::{SpotifyAccounts, listen_for_redirects},
+    youtube_quota::QuotaMeter,
+    sources::{SourceRegistry, local::LocalSource, spotify::SpotifySource, youtube::YouTubeSource, http::HttpSource}
+};
 use serenity::prelude::*;
 use songbird::SerenityInit;
 use tokio::time::sleep;
-use std::time::Duration;
+use std::{time::Duration, path::PathBuf, sync::Arc};
 use google_youtube3::{YouTube, oauth2, hyper::Client, hyper_rustls::HttpsConnectorBuilder};
-use spotify_to_query::auth;
 
 #[tokio::main]
 async fn main() {
     dotenv().unwrap();
-    let youtube_secret = oauth2::ServiceAccountKey {
-        key_type: Some(env::var("YOUTUBE_KEY_TYPE").unwrap()),
-        project_id: Some(env::var("YOUTUBE_PROJECT_ID").unwrap()),
-        private_key_id: Some(env::var("YOUTUBE_PRIVATE_KEY_ID").unwrap()),
-        private_key: env::var("YOUTUBE_PRIVATE_KEY").unwrap(),
-        client_email: env::var("YOUTUBE_CLIENT_EMAIL").unwrap(),
-        client_id: Some(env::var("YOUTUBE_CLIENT_ID").unwrap()),
-        auth_uri: Some(env::var("YOUTUBE_AUTH_URI").unwrap()),
-        token_uri: env::var("YOUTUBE_TOKEN_URI").unwrap(),
-        auth_provider_x509_cert_url: Some(env::var("YOUTUBE_AUTH_PROVIDER_X509_CERT_URL").unwrap()),
-        client_x509_cert_url: Some(env::var("YOUTUBE_CLIENT_X509_CERT_URL").unwrap())
+    let youtube_client = match youtube_secret() {
+        Some(youtube_secret) => {
+            let youtube_auth = oauth2::ServiceAccountAuthenticator::builder(youtube_secret).build().await.unwrap();
+            Some(YouTube::new(Client::builder().build(HttpsConnectorBuilder::new().with_native_roots().https_or_http().enable_http1().enable_http2().build()), youtube_auth))
+        },
+        None => {
+            println!("No YouTube Data API Credentials, Resolving YouTube Links With yt-dlp");
+            None
+        }
     };
 
-    let youtube_auth = oauth2::ServiceAccountAuthenticator::builder(youtube_secret).build().await.unwrap();
+    let spotify_client = auth().await.unwrap();
 
-    let youtube_client = YouTube::new(Client::builder().build(HttpsConnectorBuilder::new().with_native_roots().https_or_http().enable_http1().enable_http2().build()), youtube_auth);
+    let spotify_tokens_path = env::var("SPOTIFY_TOKENS_PATH").unwrap_or("spotify_tokens.json".to_owned());
+    let spotify_accounts = Arc::new(SpotifyAccounts::from_env(PathBuf::from(spotify_tokens_path)).unwrap());
+    let redirect_accounts = spotify_accounts.clone();
+    tokio::spawn(async move {
+        if let Err(err) = listen_for_redirects(redirect_accounts).await {
+            println!("Spotify redirect listener stopped: {}", err);
+        }
+    });
 
-    let spotify_client = auth().await.unwrap();
+    let youtube_quota_path = env::var("YOUTUBE_QUOTA_PATH").unwrap_or("youtube_quota.json".to_owned());
+    let youtube_quota = Arc::new(QuotaMeter::from_env(PathBuf::from(youtube_quota_path)));
+
+    let music_directory = env::var("MUSIC_DIRECTORY").ok().map(PathBuf::from);
+
+    // Checked in order, links no other source claims go to yt-dlp through the http source
+    let sources = SourceRegistry::new(vec![
+        Box::new(LocalSource::new(music_directory)),
+        Box::new(SpotifySource::new(spotify_client, spotify_accounts.clone())),
+        Box::new(YouTubeSource::new(youtube_client, youtube_quota.clone())),
+        Box::new(HttpSource)
+    ]);
 
     let token = env::var("DISCORD_TOKEN_TESTS").unwrap();
     let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT
@@ -46,6 +63,7 @@ async fn main() {
         .options(poise::FrameworkOptions { 
             commands: vec![
                 commands::play::play(),
+                commands::playnext::playnext(),
                 commands::song::song(),
                 commands::leave::leave(),
                 commands::skip::skip(),
@@ -53,7 +71,20 @@ async fn main() {
                 commands::loopc::loopc(),
                 commands::pause::pause(),
                 commands::resume::resume(),
-                commands::stop::stop()
+                commands::stop::stop(),
+                commands::import::import(),
+                commands::spotify::spotify(),
+                commands::quota::quota(),
+                commands::search::search(),
+                commands::remove::remove(),
+                commands::move_track::move_track(),
+                commands::swap::swap(),
+                commands::skipto::skipto(),
+                commands::shuffle::shuffle(),
+                commands::dedupe::dedupe(),
+                commands::sort::sort(),
+                commands::history::history(),
+                commands::previous::previous()
             ],
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("-".to_owned()),
@@ -68,7 +99,7 @@ async fn main() {
             Box::pin(async move {
                 println!("{} Has Connected To Discord", ready.user.tag());
                 poise::builtins::register_in_guild(&ctx.http, &framework.options().commands, serenity::model::id::GuildId(883721114604404757)).await?;
-                Ok(Data::new(youtube_client, spotify_client))
+                Ok(Data::new(sources, spotify_accounts, youtube_quota))
             })
         })
         .client_settings(|client_settings| client_settings.register_songbird()
@@ -77,6 +108,22 @@ async fn main() {
     framework.run().await.unwrap();
 }
 
+/// The service account from the environment, `None` when the required parts of it aren't set
+fn youtube_secret() -> Option<oauth2::ServiceAccountKey> {
+    Some(oauth2::ServiceAccountKey {
+        key_type: env::var("YOUTUBE_KEY_TYPE").ok(),
+        project_id: env::var("YOUTUBE_PROJECT_ID").ok(),
+        private_key_id: env::var("YOUTUBE_PRIVATE_KEY_ID").ok(),
+        private_key: env::var("YOUTUBE_PRIVATE_KEY").ok()?,
+        client_email: env::var("YOUTUBE_CLIENT_EMAIL").ok()?,
+        client_id: env::var("YOUTUBE_CLIENT_ID").ok(),
+        auth_uri: env::var("YOUTUBE_AUTH_URI").ok(),
+        token_uri: env::var("YOUTUBE_TOKEN_URI").ok()?,
+        auth_provider_x509_cert_url: env::var("YOUTUBE_AUTH_PROVIDER_X509_CERT_URL").ok(),
+        client_x509_cert_url: env::var("YOUTUBE_CLIENT_X509_CERT_URL").ok()
+    })
+}
+
 async fn post_command<'a>(ctx: Context<'a>) {
     let mut cleanup_guard = ctx.data().cleanup.lock().await;
     let cleanup = &mut *cleanup_guard;
@@ -0,0 +1,129 @@
+const MAX_REPORT_LENGTH: usize = 3900;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportEntry {
+    pub query: String,
+    /// `#EXTINF` / `TitleN=` title, a better search than a path that only exists on someone's disk
+    pub title: Option<String>
+}
+
+/// Reads `.pls` files by their `FileN` entries and anything else as M3U or plain text, one link or query per line
+pub fn parse_entries(content: &str, filename: &str) -> Vec<ImportEntry> {
+    if filename.to_ascii_lowercase().ends_with(".pls") {
+        return parse_pls(content);
+    }
+
+    let mut entries: Vec<ImportEntry> = vec![];
+    let mut title: Option<String> = None;
+    for line in content.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            title = extinf.split_once(',').map(|(_, title)| title.trim().to_owned()).filter(|title| !title.is_empty());
+        } else if !line.starts_with('#') {
+            entries.push(ImportEntry { query: line.to_owned(), title: title.take() });
+        }
+    }
+    entries
+}
+
+/// Entries are ordered by their number rather than where they are in the file, titles without a file are dropped
+fn parse_pls(content: &str) -> Vec<ImportEntry> {
+    let mut files: Vec<(usize, String)> = vec![];
+    let mut titles: Vec<(usize, String)> = vec![];
+    for (key, value) in content.lines().filter_map(|line| line.trim().split_once('=')) {
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim().to_owned();
+        if let Some(number) = key.strip_prefix("file").and_then(|number| number.parse::<usize>().ok()) {
+            files.push((number, value));
+        } else if let Some(number) = key.strip_prefix("title").and_then(|number| number.parse::<usize>().ok()) {
+            titles.push((number, value));
+        }
+    }
+    files.sort_by_key(|(number, _)| *number);
+
+    files.into_iter().map(|(number, query)| {
+        let title = titles.iter().find(|(title_number, _)| *title_number == number).map(|(_, title)| title.clone()).filter(|title| !title.is_empty());
+        ImportEntry { query, title }
+    }).collect()
+}
+
+/// Joins the report lines, cutting it short to fit in an embed description
+pub fn truncate_report(report: Vec<String>) -> String {
+    let mut description = String::new();
+    for (index, line) in report.iter().enumerate() {
+        if description.len() + line.len() > MAX_REPORT_LENGTH {
+            description += format!("*...and {} more lines*", report.len() - index).as_str();
+            break;
+        }
+        description += line.as_str();
+        description.push('\n');
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(query: &str, title: Option<&str>) -> ImportEntry {
+        ImportEntry { query: query.to_owned(), title: title.map(|title| title.to_owned()) }
+    }
+
+    #[test]
+    fn reads_m3u_and_plain_text() {
+        let cases = vec![
+            (
+                "#EXTM3U\r\n#EXTINF:212,Rick Astley - Never Gonna Give You Up\r\nC:\\Music\\never.mp3\r\n\r\nhttps://youtu.be/dQw4w9WgXcQ\r\n",
+                vec![entry("C:\\Music\\never.mp3", Some("Rick Astley - Never Gonna Give You Up")), entry("https://youtu.be/dQw4w9WgXcQ", None)]
+            ),
+            (
+                "#EXTINF:-1,Some Radio\n# a comment between the title and its file\n  http://radio.example.org/live  \n",
+                vec![entry("http://radio.example.org/live", Some("Some Radio"))]
+            ),
+            (
+                "#EXTINF:100,\nno title.mp3\n#EXTINF:100,Dangling Title",
+                vec![entry("no title.mp3", None)]
+            ),
+            (
+                "never gonna give you up\n\n   \nartist - song\n",
+                vec![entry("never gonna give you up", None), entry("artist - song", None)]
+            ),
+            ("", vec![]),
+        ];
+
+        for (content, expected) in cases {
+            assert_eq!(parse_entries(content, "playlist.m3u"), expected, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn reads_pls() {
+        let cases = vec![
+            (
+                "[playlist]\r\nFile1=first.mp3\r\nTitle1=First\r\nFile2=second.mp3\r\nNumberOfEntries=2\r\nVersion=2\r\n",
+                vec![entry("first.mp3", Some("First")), entry("second.mp3", None)]
+            ),
+            (
+                "[playlist]\nTitle10=Tenth\nFile10=tenth.mp3\nFile2=second.mp3\nFile1=first.mp3\nTitle2=Second\n",
+                vec![entry("first.mp3", None), entry("second.mp3", Some("Second")), entry("tenth.mp3", Some("Tenth"))]
+            ),
+            (
+                "[playlist]\n; a comment\nTitle1=Title Without a File\nFile2 = second.mp3 \nLength2=-1\n",
+                vec![entry("second.mp3", None)]
+            ),
+        ];
+
+        for (content, expected) in cases {
+            assert_eq!(parse_entries(content, "Station.PLS"), expected, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn cuts_long_reports_short() {
+        assert_eq!(truncate_report(vec!["1. first".to_owned(), "2. second".to_owned()]), "1. first\n2. second\n");
+
+        let report: Vec<String> = (0..100).map(|index| format!("{}. {}", index, "a".repeat(100))).collect();
+        let description = truncate_report(report);
+        assert!(description.len() <= MAX_REPORT_LENGTH + 30);
+        assert!(description.ends_with("*...and 63 more lines*"), "{}", description);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::{ HashMap, VecDeque },
+    sync::atomic::{ AtomicU64, Ordering },
+    time::{ Duration, Instant }
+};
+use tokio::{ sync::Mutex, time::sleep };
+use crate::{ error::Error, youtube_scraper::{ search, SearchResult } };
+
+const DEBOUNCE: Duration = Duration::from_millis(350);
+const CACHE_TTL: Duration = Duration::from_secs(600);
+const MAX_CACHED_QUERIES: usize = 500;
+const MAX_RECENT_QUERIES: usize = 10;
+
+/// State behind `/play` suggestions: searches cached by what was typed so far and each guild's recent queries
+pub struct QueryAutocomplete {
+    debounce: Duration,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, Vec<SearchResult>)>>,
+    recent_queries: Mutex<HashMap<u64, VecDeque<String>>>,
+    latest_requests: Mutex<HashMap<u64, u64>>,
+    next_request: AtomicU64
+}
+
+impl Default for QueryAutocomplete {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryAutocomplete {
+    pub fn new() -> Self {
+        Self::with_timings(DEBOUNCE, CACHE_TTL)
+    }
+
+    fn with_timings(debounce: Duration, cache_ttl: Duration) -> Self {
+        Self {
+            debounce,
+            cache_ttl,
+            cache: Mutex::new(HashMap::new()),
+            recent_queries: Mutex::new(HashMap::new()),
+            latest_requests: Mutex::new(HashMap::new()),
+            next_request: AtomicU64::new(0)
+        }
+    }
+
+    /// Moves the query to the front of the guild's recents
+    pub async fn record_query(&self, guild_id: u64, query: &str) {
+        let query = query.trim();
+        let mut recent_queries = self.recent_queries.lock().await;
+        let guild_queries = recent_queries.entry(guild_id).or_default();
+        guild_queries.retain(|recent_query| !recent_query.eq_ignore_ascii_case(query));
+        guild_queries.push_front(query.to_owned());
+        guild_queries.truncate(MAX_RECENT_QUERIES);
+    }
+
+    /// The guild's recent queries containing `partial`, newest first
+    pub async fn recent_queries(&self, guild_id: u64, partial: &str) -> Vec<String> {
+        let partial = partial.trim().to_lowercase();
+        match self.recent_queries.lock().await.get(&guild_id) {
+            Some(guild_queries) => guild_queries.iter().filter(|query| query.to_lowercase().contains(&partial)).cloned().collect(),
+            None => vec![]
+        }
+    }
+
+    /// Waits for the user to stop typing, `false` when a later keystroke of theirs took over
+    pub async fn debounce(&self, user_id: u64) -> bool {
+        let request = self.next_request.fetch_add(1, Ordering::SeqCst);
+        self.latest_requests.lock().await.insert(user_id, request);
+        sleep(self.debounce).await;
+        self.latest_requests.lock().await.get(&user_id) == Some(&request)
+    }
+
+    pub async fn cached_results(&self, partial: &str) -> Option<Vec<SearchResult>> {
+        match self.cache.lock().await.get(&cache_key(partial)) {
+            Some((searched_at, results)) if searched_at.elapsed() < self.cache_ttl => Some(results.clone()),
+            _ => None
+        }
+    }
+
+    pub async fn search(&self, partial: &str) -> Result<Vec<SearchResult>, Error> {
+        if let Some(results) = self.cached_results(partial).await {
+            return Ok(results);
+        }
+        let results = search(partial).await?;
+        self.store(partial, results.clone()).await;
+        Ok(results)
+    }
+
+    async fn store(&self, partial: &str, results: Vec<SearchResult>) {
+        let mut cache = self.cache.lock().await;
+        let cache_ttl = self.cache_ttl;
+        cache.retain(|_, (searched_at, _)| searched_at.elapsed() < cache_ttl);
+        if cache.len() >= MAX_CACHED_QUERIES {
+            if let Some(oldest) = cache.iter().min_by_key(|(_, (searched_at, _))| *searched_at).map(|(key, _)| key.clone()) {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(cache_key(partial), (Instant::now(), results));
+    }
+}
+
+fn cache_key(partial: &str) -> String {
+    partial.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(video_id: &str) -> SearchResult {
+        SearchResult { video_id: video_id.to_owned(), title: video_id.to_owned(), channel: None, duration: Some(Duration::from_secs(60)) }
+    }
+
+    #[tokio::test]
+    async fn suggests_recent_queries_newest_first() {
+        let autocomplete = QueryAutocomplete::new();
+        for query in ["daft punk", "queen", "Daft Punk Around the World", "DAFT PUNK"] {
+            autocomplete.record_query(1, query).await;
+        }
+        autocomplete.record_query(2, "daft punk one more time").await;
+
+        assert_eq!(autocomplete.recent_queries(1, "daft").await, vec!["DAFT PUNK", "Daft Punk Around the World"]);
+        assert_eq!(autocomplete.recent_queries(1, "").await.len(), 3);
+        assert_eq!(autocomplete.recent_queries(3, "").await, Vec::<String>::new());
+
+        for index in 0..20 {
+            autocomplete.record_query(1, &format!("query {}", index)).await;
+        }
+        assert_eq!(autocomplete.recent_queries(1, "").await.len(), MAX_RECENT_QUERIES);
+        assert_eq!(autocomplete.recent_queries(1, "").await[0], "query 19");
+    }
+
+    #[tokio::test]
+    async fn caches_by_what_was_typed() {
+        let autocomplete = QueryAutocomplete::with_timings(Duration::ZERO, Duration::from_millis(50));
+        autocomplete.store("never gonna", vec![result("dQw4w9WgXcQ")]).await;
+
+        assert_eq!(autocomplete.cached_results(" Never  Gonna ").await, Some(vec![result("dQw4w9WgXcQ")]));
+        assert_eq!(autocomplete.cached_results("never gonna give").await, None);
+        sleep(Duration::from_millis(60)).await;
+        assert_eq!(autocomplete.cached_results("never gonna").await, None);
+    }
+
+    #[tokio::test]
+    async fn only_the_last_keystroke_searches() {
+        let autocomplete = QueryAutocomplete::with_timings(Duration::from_millis(30), CACHE_TTL);
+        let (first, second, other_user) = tokio::join!(
+            autocomplete.debounce(1),
+            async {
+                sleep(Duration::from_millis(10)).await;
+                autocomplete.debounce(1).await
+            },
+            autocomplete.debounce(2)
+        );
+        assert_eq!((first, second, other_user), (false, true, true));
+    }
+}
@@ -0,0 +1,230 @@
+use std::{ collections::{ HashSet, VecDeque }, hash::Hash, ops::RangeInclusive };
+use rand::{ seq::SliceRandom, Rng };
+use crate::{ queue_error, error::QueueError, sources::youtube::{ parse_link, YouTubeLink } };
+
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum SortOrder {
+    #[name = "title"]
+    Title,
+    #[name = "duration"]
+    Duration,
+    #[name = "added by"]
+    AddedBy
+}
+
+/// Reads `5` or `3-7`, positions being the numbers `/queue` shows with the current track at 0
+pub fn parse_positions(input: &str) -> Result<RangeInclusive<usize>, QueueError> {
+    let parse = |position: &str| position.trim().parse::<usize>().map_err(|_| queue_error!(InvalidRange, range: input.trim()));
+    let (start, end) = match input.split_once('-') {
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => {
+            let position = parse(input)?;
+            (position, position)
+        }
+    };
+    if start > end {
+        return Err(queue_error!(InvalidRange, range: input.trim()));
+    }
+    Ok(start..=end)
+}
+
+/// Only queued up tracks can be edited, the current one is left to `/skip`
+pub fn check_position<T>(queue: &VecDeque<T>, position: usize) -> Result<(), QueueError> {
+    if queue.len() <= 1 {
+        return Err(queue_error!(EmptyQueue));
+    }
+    if position == 0 || position >= queue.len() {
+        return Err(queue_error!(InvalidPosition, position: position, len: queue.len()));
+    }
+    Ok(())
+}
+
+/// Takes the tracks out of the queue, they still have to be stopped
+pub fn remove_tracks<T>(queue: &mut VecDeque<T>, positions: RangeInclusive<usize>) -> Result<Vec<T>, QueueError> {
+    check_position(queue, *positions.start())?;
+    check_position(queue, *positions.end())?;
+    Ok(queue.drain(positions).collect())
+}
+
+pub fn move_track<T>(queue: &mut VecDeque<T>, from: usize, to: usize) -> Result<(), QueueError> {
+    check_position(queue, from)?;
+    check_position(queue, to)?;
+    if let Some(track) = queue.remove(from) {
+        queue.insert(to, track);
+    }
+    Ok(())
+}
+
+pub fn swap_tracks<T>(queue: &mut VecDeque<T>, first: usize, second: usize) -> Result<(), QueueError> {
+    check_position(queue, first)?;
+    check_position(queue, second)?;
+    queue.swap(first, second);
+    Ok(())
+}
+
+/// Takes out the tracks between the current one and `position`, skipping the current one is left to the caller
+pub fn skip_to<T>(queue: &mut VecDeque<T>, position: usize) -> Result<Vec<T>, QueueError> {
+    check_position(queue, position)?;
+    Ok(queue.drain(1..position).collect())
+}
+
+/// Moves the last `count` tracks, just enqueued at the tail, to `position` keeping their order and returns where they landed
+pub fn insert_enqueued<T>(queue: &mut VecDeque<T>, count: usize, position: usize) -> usize {
+    let tail_position = queue.len().saturating_sub(count);
+    // Into an empty queue the first track is already playing
+    if tail_position == 0 {
+        return 0;
+    }
+    let position = position.clamp(1, tail_position);
+    let enqueued: Vec<T> = queue.drain(tail_position..).collect();
+    for (offset, track) in enqueued.into_iter().enumerate() {
+        queue.insert(position + offset, track);
+    }
+    position
+}
+
+/// Shuffles everything after the current track
+pub fn shuffle_queue<T, R: Rng>(queue: &mut VecDeque<T>, rng: &mut R) {
+    if queue.len() > 2 {
+        queue.make_contiguous()[1..].shuffle(rng);
+    }
+}
+
+/// Takes out every queued track whose key an earlier track, the current one included, already has
+pub fn dedupe_queue<T, K: Eq + Hash>(queue: &mut VecDeque<T>, key: impl Fn(&T) -> Option<K>) -> Vec<T> {
+    let mut seen: HashSet<K> = HashSet::new();
+    let mut kept: VecDeque<T> = VecDeque::with_capacity(queue.len());
+    let mut duplicates: Vec<T> = vec![];
+    for (position, track) in queue.drain(..).enumerate() {
+        let is_duplicate = key(&track).is_some_and(|track_key| !seen.insert(track_key));
+        if is_duplicate && position != 0 {
+            duplicates.push(track);
+        } else {
+            kept.push_back(track);
+        }
+    }
+    *queue = kept;
+    duplicates
+}
+
+/// The same video is linked in more than one way, so YouTube links compare by video id
+pub fn dedupe_key(source_url: &str) -> Option<String> {
+    if source_url.is_empty() {
+        return None;
+    }
+    match parse_link(source_url) {
+        Ok(YouTubeLink::Video(id, _)) | Ok(YouTubeLink::PlaylistVideo(id, _, _)) => Some(format!("youtube:{}", id)),
+        _ => Some(source_url.to_owned())
+    }
+}
+
+/// Stable sort of everything after the current track, tracks without a key go last
+pub fn sort_queue<T, K: Ord>(queue: &mut VecDeque<T>, key: impl Fn(&T) -> Option<K>) {
+    if queue.len() > 2 {
+        queue.make_contiguous()[1..].sort_by_cached_key(|track| {
+            let track_key = key(track);
+            (track_key.is_none(), track_key)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(len: u32) -> VecDeque<u32> {
+        (0..len).collect()
+    }
+
+    #[test]
+    fn parses_positions_and_ranges() {
+        assert_eq!(parse_positions("4").unwrap(), 4..=4);
+        assert_eq!(parse_positions(" 3 - 7 ").unwrap(), 3..=7);
+        assert!(matches!(parse_positions("7-3"), Err(QueueError::InvalidRange {..})));
+        assert!(matches!(parse_positions("three"), Err(QueueError::InvalidRange {..})));
+        assert!(matches!(parse_positions("-2"), Err(QueueError::InvalidRange {..})));
+    }
+
+    #[test]
+    fn removes_queued_tracks_only() {
+        let mut tracks = queue(6);
+        assert_eq!(remove_tracks(&mut tracks, 2..=4).unwrap(), vec![2, 3, 4]);
+        assert_eq!(tracks, vec![0, 1, 5]);
+
+        assert!(matches!(remove_tracks(&mut tracks, 0..=1), Err(QueueError::InvalidPosition {position: 0, ..})));
+        assert!(matches!(remove_tracks(&mut tracks, 1..=3), Err(QueueError::InvalidPosition {position: 3, ..})));
+        assert!(matches!(remove_tracks(&mut queue(1), 1..=1), Err(QueueError::EmptyQueue {..})));
+        assert_eq!(tracks, vec![0, 1, 5]);
+    }
+
+    #[test]
+    fn moves_and_swaps_tracks() {
+        let mut tracks = queue(5);
+        move_track(&mut tracks, 4, 1).unwrap();
+        assert_eq!(tracks, vec![0, 4, 1, 2, 3]);
+        move_track(&mut tracks, 1, 4).unwrap();
+        assert_eq!(tracks, vec![0, 1, 2, 3, 4]);
+        swap_tracks(&mut tracks, 1, 3).unwrap();
+        assert_eq!(tracks, vec![0, 3, 2, 1, 4]);
+
+        assert!(move_track(&mut tracks, 1, 5).is_err());
+        assert!(swap_tracks(&mut tracks, 0, 2).is_err());
+        assert_eq!(tracks, vec![0, 3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn inserts_enqueued_tracks_in_order() {
+        let mut tracks = queue(6);
+        assert_eq!(insert_enqueued(&mut tracks, 2, 1), 1);
+        assert_eq!(tracks, vec![0, 4, 5, 1, 2, 3]);
+
+        let mut tracks = queue(6);
+        assert_eq!(insert_enqueued(&mut tracks, 1, 3), 3);
+        assert_eq!(tracks, vec![0, 1, 2, 5, 3, 4]);
+
+        let mut tracks = queue(6);
+        assert_eq!(insert_enqueued(&mut tracks, 2, 100), 4);
+        assert_eq!(tracks, queue(6));
+        assert_eq!(insert_enqueued(&mut tracks, 6, 1), 0);
+        assert_eq!(tracks, queue(6));
+    }
+
+    #[test]
+    fn shuffles_around_the_current_track() {
+        let mut rng = rand::rngs::mock::StepRng::new(0, 1);
+        let mut tracks = queue(20);
+        shuffle_queue(&mut tracks, &mut rng);
+        assert_eq!(tracks[0], 0);
+        let mut shuffled: Vec<u32> = tracks.iter().copied().collect();
+        shuffled.sort();
+        assert_eq!(shuffled, queue(20).into_iter().collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn dedupes_by_key() {
+        let mut tracks: VecDeque<&str> = VecDeque::from(vec!["a", "b", "a", "", "c", "b", ""]);
+        let duplicates = dedupe_queue(&mut tracks, |track| Some(*track).filter(|track| !track.is_empty()));
+        assert_eq!(duplicates, vec!["a", "b"]);
+        assert_eq!(tracks, vec!["a", "b", "", "c", ""]);
+
+        assert_eq!(dedupe_key("https://youtu.be/dQw4w9WgXcQ"), dedupe_key("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL0123456789"));
+        assert_eq!(dedupe_key("/music/song.flac").as_deref(), Some("/music/song.flac"));
+        assert_eq!(dedupe_key(""), None);
+    }
+
+    #[test]
+    fn sorts_queued_tracks() {
+        let mut tracks: VecDeque<(&str, Option<u32>)> = VecDeque::from(vec![("current", Some(9)), ("c", Some(3)), ("live", None), ("a", Some(1)), ("b", Some(3))]);
+        sort_queue(&mut tracks, |track| track.1);
+        assert_eq!(tracks.iter().map(|track| track.0).collect::<Vec<&str>>(), vec!["current", "a", "c", "b", "live"]);
+    }
+
+    #[test]
+    fn skips_to_a_track() {
+        let mut tracks = queue(5);
+        assert_eq!(skip_to(&mut tracks, 3).unwrap(), vec![1, 2]);
+        assert_eq!(tracks, vec![0, 3, 4]);
+        assert_eq!(skip_to(&mut tracks, 1).unwrap(), Vec::<u32>::new());
+        assert!(skip_to(&mut tracks, 3).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::{ future::Future, time::Duration };
+use tokio::time::sleep;
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub attempts: u32,
+    pub base_delay: Duration
+}
+
+pub static DEFAULT_BACKOFF: Backoff = Backoff { attempts: 3, base_delay: Duration::from_millis(500) };
+
+impl Backoff {
+    /// Retries `request` while it fails with transient errors, doubling the delay after every attempt
+    pub async fn retry<T, F, Fut>(&self, mut request: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>
+    {
+        let mut delay = self.base_delay;
+        let mut attempt = 1;
+        loop {
+            match request().await {
+                Err(err) if attempt < self.attempts && is_transient(&err) => {
+                    sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                },
+                result => return result
+            }
+        }
+    }
+}
+
+/// Network failures and server side errors, as opposed to quota or bad requests that won't change by retrying
+pub fn is_transient(error: &Error) -> bool {
+    if let Some(error) = error.downcast_ref::<google_youtube3::Error>() {
+        return match error {
+            google_youtube3::Error::HttpError(_) | google_youtube3::Error::Io(_) => true,
+            google_youtube3::Error::Failure(response) => is_transient_status(response.status().as_u16()),
+            google_youtube3::Error::BadRequest(value) => value.pointer("/error/code")
+                .and_then(serde_json::Value::as_u64)
+                .is_some_and(|code| is_transient_status(code as u16)),
+            _ => false
+        };
+    }
+    if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+        return error.is_timeout() || error.is_connect() || error.status().is_some_and(|status| is_transient_status(status.as_u16()));
+    }
+    if let Some(error) = error.downcast_ref::<std::io::Error>() {
+        return matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::Interrupted);
+    }
+    false
+}
+
+fn is_transient_status(status: u16) -> bool {
+    status == 429 || status >= 500
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{ AtomicU32, Ordering };
+
+    static BACKOFF: Backoff = Backoff { attempts: 3, base_delay: Duration::from_millis(1) };
+
+    fn server_error(code: u64) -> Error {
+        google_youtube3::Error::BadRequest(serde_json::json!({"error": {"code": code, "message": "backendError"}})).into()
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let attempts = AtomicU32::new(0);
+        let result = BACKOFF.retry(|| async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(server_error(503)),
+                attempt => Ok(attempt)
+            }
+        }).await;
+        assert_eq!(result.unwrap(), 2);
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), Error> = BACKOFF.retry(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(server_error(500))
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_permanent_errors() {
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), Error> = BACKOFF.retry(|| async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(server_error(403))
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert!(!is_transient(&crate::query_error!(MissingSpotifyId).into()));
+    }
+}
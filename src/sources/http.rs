@@ -0,0 +1,41 @@
+use poise::async_trait;
+use crate::{
+    error::Error,
+    http_stream,
+    convert_query::{ parse_url, ytdl_url_metadata, LazyQueued, QueryOptions }
+};
+use super::{ QueuedItem, Source };
+
+/// Any other link, played directly when it's audio and left for yt-dlp to figure out otherwise
+pub struct HttpSource;
+
+#[async_trait]
+impl Source for HttpSource {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        matches!(parse_url(query), Some(Ok(_)))
+    }
+
+    async fn resolve(&self, query: &str, _options: &QueryOptions) -> Result<Vec<QueuedItem>, Error> {
+        let url = match parse_url(query) {
+            Some(url) => url?.to_string(),
+            None => query.to_owned()
+        };
+        match http_stream::probe(&url).await {
+            Ok(Some(http_audio)) => {
+                let stream_url = if http_audio.is_live { Some(http_audio.url.clone()) } else { None };
+                return Ok(vec![QueuedItem { lazy_queued: LazyQueued::Direct(http_audio.metadata), track_data: None, stream_url }]);
+            },
+            Ok(None) => (),
+            Err(err) if http_stream::is_private_host(&err) => return Err(err),
+            // Sites that turn away plain HTTP clients are often still readable by yt-dlp
+            Err(err) => println!("Couldn't probe {}, trying yt-dlp: {}", url, err)
+        }
+
+        let metadata = ytdl_url_metadata(&url).await?;
+        Ok(metadata.into_iter().map(|entry_metadata| QueuedItem::new(LazyQueued::Lazy(entry_metadata, None))).collect())
+    }
+}
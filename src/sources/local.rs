@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+use poise::async_trait;
+use crate::{
+    query_error,
+    error::Error,
+    local_library::{ resolve_path, audio_files, extract_local_metadata },
+    convert_query::{ LazyQueued, QueryOptions }
+};
+use super::{ QueuedItem, Source };
+
+/// `local:<path>` inside the configured music directory
+pub struct LocalSource {
+    music_directory: Option<PathBuf>
+}
+
+impl LocalSource {
+    pub fn new(music_directory: Option<PathBuf>) -> Self {
+        Self { music_directory }
+    }
+}
+
+#[async_trait]
+impl Source for LocalSource {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        query.starts_with("local:")
+    }
+
+    async fn resolve(&self, query: &str, _options: &QueryOptions) -> Result<Vec<QueuedItem>, Error> {
+        let path = query.trim_start_matches("local:").trim();
+        let music_directory = self.music_directory.as_ref().ok_or(query_error!(LocalLibraryDisabled))?;
+        let files = audio_files(&resolve_path(music_directory, path)?)?;
+        if files.is_empty() {
+            return Err(query_error!(NoLocalAudio, path: path).into());
+        }
+
+        let mut items: Vec<QueuedItem> = vec![];
+        for file in files {
+            items.push(QueuedItem::new(LazyQueued::Direct(extract_local_metadata(&file).await?)));
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::QueryError;
+
+    #[tokio::test]
+    async fn needs_a_music_directory() {
+        let source = LocalSource::new(None);
+        assert!(source.matches("local:album"));
+        assert!(!source.matches("https://example.org/local:album"));
+
+        let error = source.resolve("local:album", &QueryOptions::default()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::LocalLibraryDisabled {..})));
+    }
+}
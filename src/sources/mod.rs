@@ -0,0 +1,182 @@
+pub mod youtube;
+pub mod spotify;
+pub mod local;
+pub mod http;
+
+use poise::async_trait;
+use songbird::input::Restartable;
+use crate::{
+    query_error,
+    error::Error,
+    spotify_to_query::TrackData,
+    convert_query::{ parse_url, LazyQueued, QueryOptions, QueuedInput }
+};
+
+/// A track a source resolved, turned into a songbird input only once it's queued
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedItem {
+    pub lazy_queued: LazyQueued,
+    pub track_data: Option<TrackData>,
+    /// Set for internet radio, whose current song is followed through ICY metadata
+    pub stream_url: Option<String>
+}
+
+impl QueuedItem {
+    pub fn new(lazy_queued: LazyQueued) -> Self {
+        Self { lazy_queued, track_data: None, stream_url: None }
+    }
+
+    pub fn with_track_data(lazy_queued: LazyQueued, track_data: TrackData) -> Self {
+        Self { lazy_queued, track_data: Some(track_data), stream_url: None }
+    }
+
+    pub async fn into_input(self) -> Result<QueuedInput, Error> {
+        let source = self.clone();
+        let restartable = Restartable::new(self.lazy_queued, true).await?;
+        Ok(QueuedInput { input: restartable.into(), track_data: self.track_data, stream_url: self.stream_url, source: Some(source) })
+    }
+}
+
+#[async_trait]
+pub trait Source: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether this source handles the link or prefixed query
+    fn matches(&self, query: &str) -> bool;
+
+    async fn resolve(&self, query: &str, options: &QueryOptions) -> Result<Vec<QueuedItem>, Error>;
+
+    /// Plain text queries, sources that can't search return nothing
+    async fn search(&self, _query: &str) -> Result<Vec<QueuedItem>, Error> {
+        Ok(vec![])
+    }
+}
+
+/// Sources in priority order, the first one to match a query resolves it
+pub struct SourceRegistry {
+    sources: Vec<Box<dyn Source>>
+}
+
+impl SourceRegistry {
+    pub fn new(sources: Vec<Box<dyn Source>>) -> Self {
+        Self { sources }
+    }
+
+    pub fn find(&self, query: &str) -> Option<&dyn Source> {
+        let query = query.trim();
+        self.sources.iter().find(|source| source.matches(query)).map(|source| source.as_ref())
+    }
+
+    /// Text that no source claims and that isn't a link
+    pub fn is_search(&self, query: &str) -> bool {
+        self.find(query).is_none() && parse_url(query.trim()).is_none()
+    }
+
+    pub async fn resolve(&self, query: &str, options: &QueryOptions) -> Result<Vec<QueuedItem>, Error> {
+        let query = query.trim();
+        if let Some(source) = self.find(query) {
+            return source.resolve(query, options).await;
+        }
+        match parse_url(query) {
+            Some(url) => Err(query_error!(UnsupportedUrl, url: url?.as_str()).into()),
+            None => self.search(query).await
+        }
+    }
+
+    pub async fn search(&self, query: &str) -> Result<Vec<QueuedItem>, Error> {
+        for source in self.sources.iter() {
+            let items = source.search(query).await?;
+            if !items.is_empty() {
+                return Ok(items);
+            }
+        }
+        Err(query_error!(NoSearchResults, query: query).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::QueryError;
+
+    struct MockSource {
+        name: &'static str,
+        prefix: &'static str,
+        can_search: bool
+    }
+
+    #[async_trait]
+    impl Source for MockSource {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn matches(&self, query: &str) -> bool {
+            query.starts_with(self.prefix)
+        }
+
+        async fn resolve(&self, query: &str, _options: &QueryOptions) -> Result<Vec<QueuedItem>, Error> {
+            Ok(vec![QueuedItem::new(LazyQueued::Lazier(format!("{} resolved {}", self.name, query)))])
+        }
+
+        async fn search(&self, query: &str) -> Result<Vec<QueuedItem>, Error> {
+            if !self.can_search {
+                return Ok(vec![]);
+            }
+            Ok(vec![QueuedItem::new(LazyQueued::Lazier(format!("{} searched {}", self.name, query)))])
+        }
+    }
+
+    fn registry() -> SourceRegistry {
+        SourceRegistry::new(vec![
+            Box::new(MockSource { name: "first", prefix: "https://example.org/first", can_search: false }),
+            Box::new(MockSource { name: "second", prefix: "https://example.org/", can_search: true }),
+            Box::new(MockSource { name: "third", prefix: "https://example.org/", can_search: true })
+        ])
+    }
+
+    fn lazier(item: &QueuedItem) -> &str {
+        match item.lazy_queued {
+            LazyQueued::Lazier(ref query) => query,
+            _ => panic!("expected a lazier item")
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_with_the_first_matching_source() {
+        let registry = registry();
+        let options = QueryOptions::default();
+
+        let items = registry.resolve(" https://example.org/first/1 ", &options).await.unwrap();
+        assert_eq!(lazier(&items[0]), "first resolved https://example.org/first/1");
+
+        let items = registry.resolve("https://example.org/other", &options).await.unwrap();
+        assert_eq!(lazier(&items[0]), "second resolved https://example.org/other");
+        assert_eq!(registry.find("https://example.org/other").map(|source| source.name()), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn searches_plain_text_in_priority_order() {
+        let registry = registry();
+
+        let items = registry.resolve("some song", &QueryOptions::default()).await.unwrap();
+        assert_eq!(lazier(&items[0]), "second searched some song");
+        assert!(registry.is_search("some song"));
+        assert!(!registry.is_search("https://example.org/first"));
+
+        let error = SourceRegistry::new(vec![]).resolve("some song", &QueryOptions::default()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::NoSearchResults {..})));
+    }
+
+    #[tokio::test]
+    async fn rejects_links_no_source_claims() {
+        let registry = registry();
+
+        let error = registry.resolve("https://unknown.example.com/track", &QueryOptions::default()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::UnsupportedUrl {..})));
+        assert!(!registry.is_search("https://unknown.example.com/track"));
+
+        let error = registry.resolve("ftp://example.org/track.mp3", &QueryOptions::default()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::UnsupportedScheme {..})));
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+use poise::async_trait;
+use rspotify::ClientCredsSpotify;
+use crate::{
+    query_error,
+    error::Error,
+    spotify_accounts::SpotifyAccounts,
+    spotify_to_query::{
+        TrackData, extract_track_query, extract_playlist_queries, extract_liked_queries, extract_album_queries,
+        extract_artist_top_track_queries, extract_artist_latest_album_queries
+    },
+    track_matcher::{ match_track, MatchedCandidate },
+    convert_query::{ parse_url, url_host, ArtistMode, LazyQueued, QueryOptions }
+};
+use super::{ QueuedItem, Source };
+
+pub static SPOTIFY_HOSTS: [&str; 2] = ["open.spotify.com", "www.open.spotify.com"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpotifyLink {
+    Track(String),
+    Playlist(String),
+    Album(String),
+    Artist(String),
+    /// `spotify:liked`, the Liked Songs of whoever queued it
+    Liked
+}
+
+/// Reads an `open.spotify.com` link or `spotify:liked`
+pub fn parse_link(query: &str) -> Result<SpotifyLink, Error> {
+    let query = query.trim();
+    if query.eq_ignore_ascii_case("spotify:liked") {
+        return Ok(SpotifyLink::Liked);
+    }
+
+    let url = match parse_url(query) {
+        Some(url) => url?,
+        None => return Err(query_error!(MissingSpotifyId).into())
+    };
+    let argumets = url.path_segments().map(|f| f.collect::<Vec<&str>>()).ok_or(query_error!(MissingSpotifyId))?;
+    let content_type = argumets.first();
+    let id = argumets.get(1).filter(|id| !id.is_empty());
+
+    if let (Some(content_type), Some(id)) = (content_type, id) {
+        return Ok(match *content_type {
+            "track" => SpotifyLink::Track((*id).to_owned()),
+            "playlist" => SpotifyLink::Playlist((*id).to_owned()),
+            "album" => SpotifyLink::Album((*id).to_owned()),
+            "artist" => SpotifyLink::Artist((*id).to_owned()),
+            "episode" | "show" => return Err(query_error!(SpotifyPodcast).into()),
+            _ => return Err(query_error!(UnknownSpotifyContentType, content_type: *content_type).into())
+        });
+    }
+
+    Err(query_error!(MissingSpotifyId).into())
+}
+
+pub struct SpotifySource {
+    client: ClientCredsSpotify,
+    accounts: Arc<SpotifyAccounts>
+}
+
+impl SpotifySource {
+    pub fn new(client: ClientCredsSpotify, accounts: Arc<SpotifyAccounts>) -> Self {
+        Self { client, accounts }
+    }
+
+    /// Uses the user's linked account when there is one, so their private playlists work too.
+    /// A link that stopped working still leaves public playlists playable through the bot's own client
+    async fn playlist_queries(&self, id: &str, options: &QueryOptions) -> Result<Vec<TrackData>, Error> {
+        if let Some(user_id) = options.user_id {
+            match self.accounts.user_client(user_id).await {
+                Ok(Some(user_client)) => return extract_playlist_queries(&user_client, id, options.progress.clone()).await,
+                Ok(None) => {},
+                Err(err) => println!("Couldn't refresh the Spotify token of {}, using the bot's client: {}", user_id, err)
+            }
+        }
+        extract_playlist_queries(&self.client, id, options.progress.clone()).await
+    }
+
+    async fn liked_queries(&self, options: &QueryOptions) -> Result<Vec<TrackData>, Error> {
+        let user_id = options.user_id.ok_or(query_error!(SpotifyNotLinked))?;
+        let user_client = self.accounts.user_client(user_id).await?.ok_or(query_error!(SpotifyNotLinked))?;
+        extract_liked_queries(&user_client, options.progress.clone()).await
+    }
+}
+
+/// Spotify tracks, matched to a YouTube video once they're about to play
+fn matched_items(tracks_data: Vec<TrackData>) -> Vec<QueuedItem> {
+    tracks_data.into_iter().map(|track_data| QueuedItem::with_track_data(LazyQueued::Matched(track_data.clone(), MatchedCandidate::default()), track_data)).collect()
+}
+
+#[async_trait]
+impl Source for SpotifySource {
+    fn name(&self) -> &'static str {
+        "spotify"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        query.eq_ignore_ascii_case("spotify:liked") || url_host(query).is_some_and(|host| SPOTIFY_HOSTS.contains(&host.as_str()))
+    }
+
+    async fn resolve(&self, query: &str, options: &QueryOptions) -> Result<Vec<QueuedItem>, Error> {
+        Ok(match parse_link(query)? {
+            SpotifyLink::Track(id) => {
+                let track_data = extract_track_query(&self.client, &id).await?;
+                // A single track is matched right away so the reply can show what was found
+                let lazy_queued = match match_track(&track_data).await? {
+                    Some(candidate) => LazyQueued::Lazy(candidate.metadata(), None),
+                    None => LazyQueued::Matched(track_data.clone(), MatchedCandidate::default())
+                };
+                vec![QueuedItem::with_track_data(lazy_queued, track_data)]
+            },
+            SpotifyLink::Playlist(id) => matched_items(self.playlist_queries(&id, options).await?),
+            SpotifyLink::Album(id) => matched_items(extract_album_queries(&self.client, &id, options.progress.clone()).await?),
+            SpotifyLink::Artist(id) => matched_items(match options.artist_mode {
+                ArtistMode::TopTracks => extract_artist_top_track_queries(&self.client, &id).await?,
+                ArtistMode::LatestAlbum => extract_artist_latest_album_queries(&self.client, &id, options.progress.clone()).await?
+            }),
+            SpotifyLink::Liked => matched_items(self.liked_queries(options).await?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::QueryError;
+
+    #[test]
+    fn classifies_spotify_urls() {
+        let cases: Vec<(&str, SpotifyLink)> = vec![
+            ("https://open.spotify.com/track/4cOdK2wGLETKBW3PvgPWqT", SpotifyLink::Track("4cOdK2wGLETKBW3PvgPWqT".to_owned())),
+            ("https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M?si=abc", SpotifyLink::Playlist("37i9dQZF1DXcBWIGoYBM5M".to_owned())),
+            ("https://open.spotify.com/album/6DEjYFkNZh67HP7R9PSZvv", SpotifyLink::Album("6DEjYFkNZh67HP7R9PSZvv".to_owned())),
+            ("https://open.spotify.com/artist/0gxyHStUsqpMadRV0Di1Qt", SpotifyLink::Artist("0gxyHStUsqpMadRV0Di1Qt".to_owned())),
+            ("spotify:liked", SpotifyLink::Liked),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(parse_link(url).unwrap(), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn returns_typed_errors() {
+        let error = parse_link("https://open.spotify.com/artist").unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::MissingSpotifyId {..})));
+
+        let error = parse_link("https://open.spotify.com/episode/512ojhOuo1ktJprKbVcKyQ").unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::SpotifyPodcast {..})));
+
+        let error = parse_link("https://open.spotify.com/user/someone").unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::UnknownSpotifyContentType {..})));
+    }
+}
@@ -0,0 +1,318 @@
+use std::{ fmt::Display, sync::Arc, time::Duration };
+use poise::async_trait;
+use songbird::input::Metadata;
+use url::Url;
+use google_youtube3::{ YouTube, hyper::client::HttpConnector, hyper_rustls::HttpsConnector };
+use crate::{
+    query_error,
+    error::Error,
+    youtube_api::{ extract_video_metadata, extract_playlist_video_metadata },
+    youtube_scraper,
+    retry::DEFAULT_BACKOFF,
+    youtube_quota::{ is_quota_exceeded, QuotaMeter },
+    convert_query::{ parse_url, url_host, ytdl_url_metadata, LazyQueued, PlaylistMode, QueryOptions }
+};
+use super::{ QueuedItem, Source };
+
+pub static YOUTUBE_HOSTS: [&str; 8] = [
+    "www.youtube.com", "youtube.com", "m.youtube.com", "music.youtube.com",
+    "www.youtube-nocookie.com", "youtube-nocookie.com", "www.youtu.be", "youtu.be"
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum YouTubeLink {
+    Video(String, Option<Duration>),
+    Playlist(String),
+    /// `watch?v=..&list=..` links, resolved according to a `PlaylistMode`
+    PlaylistVideo(String, String, Option<Duration>)
+}
+
+/// Reads a link to a video, a playlist or a video inside a playlist
+pub fn parse_link(query: &str) -> Result<YouTubeLink, Error> {
+    let url = match parse_url(query.trim()) {
+        Some(url) => url?,
+        None => return Err(query_error!(UnrecognizedYouTubeUrl, url: query).into())
+    };
+    match url.host_str() {
+        Some("www.youtu.be" | "youtu.be") => {
+            let video_id = url.path_segments().and_then(|mut segments| segments.next()).unwrap_or("");
+            video_link(&url, video_id)
+        },
+        Some(host) if YOUTUBE_HOSTS.contains(&host) => youtube_link(&url),
+        _ => Err(query_error!(UnrecognizedYouTubeUrl, url: url.as_str()).into())
+    }
+}
+
+fn youtube_link(url: &Url) -> Result<YouTubeLink, Error> {
+    let segments = url.path_segments().map(|f| f.filter(|segment| !segment.is_empty()).collect::<Vec<&str>>()).unwrap_or_default();
+    let video_id = url.query_pairs().find(|p| p.0 == "v").map(|f| f.1.into_owned());
+    let playlist_id = url.query_pairs().find(|p| p.0 == "list").map(|f| f.1.into_owned());
+
+    match (segments.as_slice(), video_id, playlist_id) {
+        (["watch"], Some(video_id), _) => video_link(url, &video_id),
+        (["watch"] | ["playlist"], None, Some(playlist_id)) => Ok(YouTubeLink::Playlist(playlist_id)),
+        (["shorts" | "embed" | "live" | "v", video_id], _, _) => video_link(url, video_id),
+        _ => Err(query_error!(UnrecognizedYouTubeUrl, url: url.as_str()).into())
+    }
+}
+
+fn video_link(url: &Url, video_id: &str) -> Result<YouTubeLink, Error> {
+    if !is_youtube_video_id(video_id) {
+        return Err(query_error!(InvalidVideoId, id: video_id).into());
+    }
+    let start_time = extract_start_time(url);
+    Ok(match url.query_pairs().find(|p| p.0 == "list") {
+        Some((_, playlist_id)) => YouTubeLink::PlaylistVideo(video_id.to_owned(), playlist_id.into_owned(), start_time),
+        None => YouTubeLink::Video(video_id.to_owned(), start_time)
+    })
+}
+
+fn is_youtube_video_id(id: &str) -> bool {
+    id.len() == 11 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn extract_start_time(url: &Url) -> Option<Duration> {
+    url.query_pairs()
+        .find(|p| p.0 == "t" || p.0 == "start")
+        .and_then(|p| parse_timestamp(&p.1))
+}
+
+/// Parses YouTube's `t=` formats: `95`, `95s`, `1m35s` and `1h2m3s`
+pub fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let mut seconds: u64 = 0;
+    let mut number = String::new();
+    for c in timestamp.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let multiplier = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None
+        };
+        seconds = seconds.checked_add(number.parse::<u64>().ok()?.checked_mul(multiplier)?)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        seconds = seconds.checked_add(number.parse::<u64>().ok()?)?;
+    }
+
+    if seconds == 0 { None } else { Some(Duration::from_secs(seconds)) }
+}
+
+/// Where video and playlist metadata comes from, in the order they're tried
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    DataApi,
+    Ytdlp,
+    Scraper
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DataApi => f.write_str("the Data API"),
+            Self::Ytdlp => f.write_str("yt-dlp"),
+            Self::Scraper => f.write_str("the scraper")
+        }
+    }
+}
+
+/// Tries the Data API when there are credentials and quota left for it, then yt-dlp and the scraper
+pub struct YouTubeSource {
+    client: Option<YouTube<HttpsConnector<HttpConnector>>>,
+    quota: Arc<QuotaMeter>
+}
+
+impl YouTubeSource {
+    pub fn new(client: Option<YouTube<HttpsConnector<HttpConnector>>>, quota: Arc<QuotaMeter>) -> Self {
+        Self { client, quota }
+    }
+
+    fn backends(&self, backends: &[Backend]) -> Vec<Backend> {
+        backends.iter().copied().filter(|backend| *backend != Backend::DataApi || self.client.is_some()).collect()
+    }
+
+    /// Keeps later requests off the Data API once YouTube says the quota ran out
+    async fn record_quota_exceeded(&self, error: &Error) {
+        if is_quota_exceeded(error) {
+            if let Err(err) = self.quota.exhaust().await {
+                println!("Couldn't save the YouTube quota usage: {}", err);
+            }
+        }
+    }
+
+    async fn video_metadata(&self, id: &str) -> Result<Metadata, Error> {
+        let url = format!("https://www.youtube.com/watch?v={}", id);
+        let mut last_error = None;
+        for backend in self.backends(&[Backend::DataApi, Backend::Ytdlp, Backend::Scraper]) {
+            let result = DEFAULT_BACKOFF.retry(|| async {
+                match backend {
+                    Backend::DataApi => extract_video_metadata(self.client.as_ref().unwrap(), &self.quota, id).await,
+                    Backend::Ytdlp => ytdl_url_metadata(&url).await?.into_iter().next()
+                        .ok_or(query_error!(InvalidVideoId, id: id).into()),
+                    Backend::Scraper => youtube_scraper::video_metadata(id).await
+                }
+            }).await;
+            match result {
+                Ok(metadata) => {
+                    println!("YouTube video {} served by {}", id, backend);
+                    return Ok(metadata);
+                },
+                Err(err) => {
+                    println!("YouTube video {} failed with {}: {}", id, backend, err);
+                    self.record_quota_exceeded(&err).await;
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error.unwrap_or(query_error!(InvalidVideoId, id: id).into()))
+    }
+
+    async fn playlist_metadata(&self, id: &str) -> Result<Vec<Metadata>, Error> {
+        let url = format!("https://www.youtube.com/playlist?list={}", id);
+        let mut last_error = None;
+        for backend in self.backends(&[Backend::DataApi, Backend::Ytdlp, Backend::Scraper]) {
+            let result = DEFAULT_BACKOFF.retry(|| async {
+                match backend {
+                    Backend::DataApi => extract_playlist_video_metadata(self.client.as_ref().unwrap(), &self.quota, id).await,
+                    Backend::Ytdlp => ytdl_url_metadata(&url).await,
+                    Backend::Scraper => youtube_scraper::playlist_metadata(id).await
+                }
+            }).await;
+            match result {
+                Ok(metadata) => {
+                    println!("YouTube playlist {} served by {}", id, backend);
+                    return Ok(metadata);
+                },
+                Err(err) => {
+                    println!("YouTube playlist {} failed with {}: {}", id, backend, err);
+                    self.record_quota_exceeded(&err).await;
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error.unwrap_or(query_error!(UnsupportedUrl, url: url).into()))
+    }
+
+    async fn video_items(&self, id: &str, start_time: Option<Duration>) -> Result<Vec<QueuedItem>, Error> {
+        let video_metadata = self.video_metadata(id).await?;
+        Ok(vec![QueuedItem::new(LazyQueued::Lazy(video_metadata, start_time))])
+    }
+
+    async fn playlist_items(&self, id: &str) -> Result<Vec<QueuedItem>, Error> {
+        let playlist_metadata = self.playlist_metadata(id).await?;
+        Ok(playlist_metadata.into_iter().map(|video_metadata| QueuedItem::new(LazyQueued::Lazy(video_metadata, None))).collect())
+    }
+}
+
+#[async_trait]
+impl Source for YouTubeSource {
+    fn name(&self) -> &'static str {
+        "youtube"
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        url_host(query).is_some_and(|host| YOUTUBE_HOSTS.contains(&host.as_str()))
+    }
+
+    async fn resolve(&self, query: &str, options: &QueryOptions) -> Result<Vec<QueuedItem>, Error> {
+        match parse_link(query)? {
+            YouTubeLink::Video(id, start_time) => self.video_items(&id, start_time).await,
+            YouTubeLink::Playlist(id) => self.playlist_items(&id).await,
+            YouTubeLink::PlaylistVideo(video_id, playlist_id, start_time) => match options.playlist_mode {
+                PlaylistMode::VideoOnly => self.video_items(&video_id, start_time).await,
+                PlaylistMode::WholePlaylist => self.playlist_items(&playlist_id).await
+            }
+        }
+    }
+
+    async fn search(&self, query: &str) -> Result<Vec<QueuedItem>, Error> {
+        Ok(vec![QueuedItem::new(LazyQueued::Search(query.to_owned()))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::QueryError;
+
+    fn video(id: &str, start_time: Option<u64>) -> YouTubeLink {
+        YouTubeLink::Video(id.to_owned(), start_time.map(Duration::from_secs))
+    }
+
+    #[test]
+    fn classifies_youtube_urls() {
+        let cases: Vec<(&str, YouTubeLink)> = vec![
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://youtube.com/watch?v=dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://m.youtube.com/watch?v=dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://music.youtube.com/watch?v=dQw4w9WgXcQ&feature=share", video("dQw4w9WgXcQ", None)),
+            ("https://www.youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=95", video("dQw4w9WgXcQ", Some(95))),
+            ("https://youtu.be/dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://youtu.be/dQw4w9WgXcQ?t=1m35s", video("dQw4w9WgXcQ", Some(95))),
+            ("https://www.youtube.com/shorts/dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://youtube.com/shorts/dQw4w9WgXcQ?feature=share", video("dQw4w9WgXcQ", None)),
+            ("https://www.youtube.com/embed/dQw4w9WgXcQ?start=95", video("dQw4w9WgXcQ", Some(95))),
+            ("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://www.youtube.com/live/dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://www.youtube.com/v/dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://www.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI", YouTubeLink::Playlist("PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI".to_owned())),
+            ("https://music.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI", YouTubeLink::Playlist("PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI".to_owned())),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI&index=2", YouTubeLink::PlaylistVideo("dQw4w9WgXcQ".to_owned(), "PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI".to_owned(), None)),
+            ("youtu.be/dQw4w9WgXcQ", video("dQw4w9WgXcQ", None)),
+            ("https://youtu.be/dQw4w9WgXcQ?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI&t=10s", YouTubeLink::PlaylistVideo("dQw4w9WgXcQ".to_owned(), "PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI".to_owned(), Some(Duration::from_secs(10)))),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(parse_link(url).unwrap(), expected, "{}", url);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_youtube_urls() {
+        let cases = vec![
+            "https://www.youtube.com/",
+            "https://www.youtube.com/watch",
+            "https://www.youtube.com/shorts/",
+            "https://www.youtube.com/embed/tooshort",
+            "https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw",
+            "https://youtu.be/",
+            "https://soundcloud.com/artist/track",
+            "never gonna give you up",
+        ];
+
+        for url in cases {
+            assert!(parse_link(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn returns_typed_errors() {
+        let error = parse_link("https://www.youtube.com/shorts/bad").unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::InvalidVideoId {..})));
+
+        let error = parse_link("https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw").unwrap_err();
+        assert!(matches!(error.downcast_ref::<QueryError>(), Some(QueryError::UnrecognizedYouTubeUrl {..})));
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        let cases = vec![
+            ("95", Some(95)),
+            ("95s", Some(95)),
+            ("1m35s", Some(95)),
+            ("1h2m3s", Some(3723)),
+            ("0", None),
+            ("abc", None),
+            ("99999999999999999h", None),
+            ("5124095576030431h18446744073709551615s", None),
+        ];
+
+        for (timestamp, expected) in cases {
+            assert_eq!(parse_timestamp(timestamp), expected.map(Duration::from_secs), "{}", timestamp);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::{ collections::HashMap, path::PathBuf, sync::Arc, time::Duration };
+use rand::{ distributions::Alphanumeric, Rng };
+use rspotify::{ prelude::*, scopes, AuthCodePkceSpotify, Credentials, OAuth, Token };
+use tokio::{
+    io::{ AsyncReadExt, AsyncWriteExt },
+    net::{ TcpListener, TcpStream },
+    sync::{ oneshot, Mutex },
+    task::spawn_blocking
+};
+use url::Url;
+use crate::{ account_error, error::Error };
+
+const MAX_REQUEST_SIZE: usize = 8192;
+pub const LINK_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct PendingLink {
+    user_id: u64,
+    client: AuthCodePkceSpotify,
+    linked: oneshot::Sender<Result<String, Error>>
+}
+
+struct RedirectParams {
+    state: String,
+    code: Option<String>,
+    error: Option<String>
+}
+
+/// Discord users who linked their Spotify account, kept as refresh tokens on disk
+pub struct SpotifyAccounts {
+    credentials: Credentials,
+    oauth: OAuth,
+    tokens_path: PathBuf,
+    refresh_tokens: Mutex<HashMap<u64, String>>,
+    /// Access tokens last handed out, reused until they expire
+    access_tokens: Mutex<HashMap<u64, Token>>,
+    pending_links: Mutex<HashMap<String, PendingLink>>
+}
+
+impl SpotifyAccounts {
+    pub fn from_env(tokens_path: PathBuf) -> Result<Self, Error> {
+        let credentials = Credentials::from_env().ok_or(account_error!(MissingEnv, name: "RSPOTIFY_CLIENT_ID"))?;
+        let oauth = OAuth::from_env(scopes!("playlist-read-private", "playlist-read-collaborative", "user-library-read"))
+            .ok_or(account_error!(MissingEnv, name: "RSPOTIFY_REDIRECT_URI"))?;
+        let refresh_tokens: HashMap<u64, String> = match std::fs::read(&tokens_path) {
+            Ok(content) => serde_json::from_slice(&content)?,
+            Err(_) => HashMap::new()
+        };
+
+        Ok(Self { credentials, oauth, tokens_path, refresh_tokens: Mutex::new(refresh_tokens), access_tokens: Mutex::new(HashMap::new()), pending_links: Mutex::new(HashMap::new()) })
+    }
+
+    pub async fn is_linked(&self, user_id: u64) -> bool {
+        self.refresh_tokens.lock().await.contains_key(&user_id)
+    }
+
+    /// Returns the authorize url and resolves to the account's display name once Spotify redirects back
+    pub async fn start_link(&self, user_id: u64) -> Result<(String, String, oneshot::Receiver<Result<String, Error>>), Error> {
+        let state: String = rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect();
+        let oauth = OAuth { state: state.clone(), ..self.oauth.clone() };
+        let mut client = AuthCodePkceSpotify::new(self.credentials.clone(), oauth);
+        let authorize_url = client.get_authorize_url(None)?;
+
+        let (linked, linked_receiver) = oneshot::channel();
+        let mut pending_links = self.pending_links.lock().await;
+        // Only the latest link attempt of a user stays valid
+        pending_links.retain(|_, pending_link| pending_link.user_id != user_id);
+        pending_links.insert(state.clone(), PendingLink { user_id, client, linked });
+        Ok((authorize_url, state, linked_receiver))
+    }
+
+    pub async fn cancel_link(&self, state: &str) {
+        self.pending_links.lock().await.remove(state);
+    }
+
+    pub async fn unlink(&self, user_id: u64) -> Result<bool, Error> {
+        let mut refresh_tokens = self.refresh_tokens.lock().await;
+        let was_linked = refresh_tokens.remove(&user_id).is_some();
+        self.access_tokens.lock().await.remove(&user_id);
+        self.save(&refresh_tokens)?;
+        Ok(was_linked)
+    }
+
+    /// A client authorized as the user, or `None` when they haven't linked an account
+    pub async fn user_client(&self, user_id: u64) -> Result<Option<AuthCodePkceSpotify>, Error> {
+        let refresh_token = match self.refresh_tokens.lock().await.get(&user_id) {
+            Some(refresh_token) => refresh_token.clone(),
+            None => return Ok(None)
+        };
+
+        let client = AuthCodePkceSpotify::new(self.credentials.clone(), self.oauth.clone());
+        if let Some(token) = self.access_tokens.lock().await.get(&user_id).filter(|token| !token.is_expired()) {
+            *client.token.lock().unwrap() = Some(token.clone());
+            return Ok(Some(client));
+        }
+
+        let refreshing_client = client.clone();
+        let token = spawn_blocking(move || {
+            let mut payload = HashMap::new();
+            payload.insert("grant_type", "refresh_token");
+            payload.insert("refresh_token", refresh_token.as_str());
+            payload.insert("client_id", refreshing_client.creds.id.as_str());
+            let mut token = refreshing_client.fetch_access_token(&payload, None)?;
+            // PKCE refresh tokens are rotated, but Spotify doesn't always send a new one
+            token.refresh_token = token.refresh_token.or(Some(refresh_token));
+            Ok::<Token, Error>(token)
+        }).await??;
+
+        if let Some(ref refresh_token) = token.refresh_token {
+            let mut refresh_tokens = self.refresh_tokens.lock().await;
+            if refresh_tokens.insert(user_id, refresh_token.clone()).as_ref() != Some(refresh_token) {
+                self.save(&refresh_tokens)?;
+            }
+        }
+        self.access_tokens.lock().await.insert(user_id, token.clone());
+        *client.token.lock().unwrap() = Some(token);
+        Ok(Some(client))
+    }
+
+    fn save(&self, refresh_tokens: &HashMap<u64, String>) -> Result<(), Error> {
+        std::fs::write(&self.tokens_path, serde_json::to_vec(refresh_tokens)?)?;
+        Ok(())
+    }
+
+    async fn finish_link(&self, params: RedirectParams) -> Result<String, Error> {
+        let pending_link = self.pending_links.lock().await.remove(&params.state)
+            .ok_or(account_error!(LinkExpired))?;
+
+        let result = match (params.code, params.error) {
+            (Some(code), _) => self.request_tokens(pending_link.user_id, pending_link.client, code).await,
+            (None, error) => Err(account_error!(AuthorizationDenied, reason: error.unwrap_or("missing code".to_owned())).into())
+        };
+        let response = match result {
+            Ok(ref display_name) => format!("Linked as {}, you can close this tab", display_name),
+            Err(_) => "Linking failed, check Discord and try again".to_owned()
+        };
+        let _ = pending_link.linked.send(result);
+        Ok(response)
+    }
+
+    async fn request_tokens(&self, user_id: u64, client: AuthCodePkceSpotify, code: String) -> Result<String, Error> {
+        let (refresh_token, display_name) = spawn_blocking(move || {
+            client.request_token(&code)?;
+            let user = client.current_user()?;
+            let refresh_token = client.token.lock().unwrap().as_ref().and_then(|token| token.refresh_token.clone());
+            Ok::<(Option<String>, String), Error>((refresh_token, user.display_name.unwrap_or(user.id.to_string())))
+        }).await??;
+
+        let refresh_token = refresh_token.ok_or(account_error!(MissingRefreshToken))?;
+        let mut refresh_tokens = self.refresh_tokens.lock().await;
+        refresh_tokens.insert(user_id, refresh_token);
+        self.save(&refresh_tokens)?;
+        // A token cached for a previously linked account mustn't outlive it
+        self.access_tokens.lock().await.remove(&user_id);
+        Ok(display_name)
+    }
+}
+
+/// Serves the OAuth redirect uri, finishing whichever `/spotify link` the state belongs to
+pub async fn listen_for_redirects(accounts: Arc<SpotifyAccounts>) -> Result<(), Error> {
+    let redirect_uri = Url::parse(&accounts.oauth.redirect_uri)?;
+    let host = redirect_uri.host_str().ok_or(account_error!(InvalidRedirectUri, uri: redirect_uri.as_str()))?.to_owned();
+    let port = redirect_uri.port_or_known_default().unwrap_or(80);
+    let listener = TcpListener::bind((host.as_str(), port)).await?;
+
+    loop {
+        let (socket, _) = listener.accept().await?;
+        let accounts = accounts.clone();
+        let redirect_uri = redirect_uri.clone();
+        tokio::spawn(async move {
+            let _ = handle_redirect(socket, &accounts, &redirect_uri).await;
+        });
+    }
+}
+
+async fn handle_redirect(mut socket: TcpStream, accounts: &SpotifyAccounts, redirect_uri: &Url) -> Result<(), Error> {
+    let mut request: Vec<u8> = vec![];
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let read = socket.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let (status, body) = match parse_redirect(&String::from_utf8_lossy(&request), redirect_uri) {
+        Some(params) => match accounts.finish_link(params).await {
+            Ok(message) => ("200 OK", message),
+            Err(_) => ("400 Bad Request", "This link expired, run /spotify link again".to_owned())
+        },
+        None => ("404 Not Found", "Not Found".to_owned())
+    };
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+    socket.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+fn parse_redirect(request: &str, redirect_uri: &Url) -> Option<RedirectParams> {
+    let target = request.lines().next()?.strip_prefix("GET ")?.split(' ').next()?;
+    let url = redirect_uri.join(target).ok()?;
+    if url.path() != redirect_uri.path() {
+        return None;
+    }
+
+    let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    Some(RedirectParams { state: query.get("state")?.clone(), code: query.get("code").cloned(), error: query.get("error").cloned() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_redirects() {
+        let redirect_uri = Url::parse("http://localhost:8888/callback").unwrap();
+
+        let params = parse_redirect("GET /callback?code=abc&state=xyz HTTP/1.1\r\nHost: localhost\r\n\r\n", &redirect_uri).unwrap();
+        assert_eq!((params.state.as_str(), params.code.as_deref(), params.error), ("xyz", Some("abc"), None));
+
+        let params = parse_redirect("GET /callback?error=access_denied&state=xyz HTTP/1.1\r\n\r\n", &redirect_uri).unwrap();
+        assert_eq!((params.code, params.error.as_deref()), (None, Some("access_denied")));
+
+        assert!(parse_redirect("GET /favicon.ico HTTP/1.1\r\n\r\n", &redirect_uri).is_none());
+        assert!(parse_redirect("GET /callback?code=abc HTTP/1.1\r\n\r\n", &redirect_uri).is_none());
+        assert!(parse_redirect("POST /callback?code=abc&state=xyz HTTP/1.1\r\n\r\n", &redirect_uri).is_none());
+    }
+}
@@ -1,74 +1,132 @@
 use rspotify::{
-    model::{ PlaylistId, TrackId, PlayableItem, AlbumId },
+    model::{ PlaylistId, TrackId, PlayableItem, AlbumId, ArtistId, AlbumType, Market, Country, FullTrack },
     prelude::*,
-    scopes, Credentials, OAuth, ClientCredsSpotify
+    Credentials, ClientCredsSpotify, AuthCodePkceSpotify
 };
-use crate::error::Error;
+use std::{ sync::Arc, time::Duration };
+use tokio::{ sync::watch, task::spawn_blocking };
+use crate::{ query_error, error::Error };
 
-#[derive(Debug)]
+/// Client credentials have no user market, so artist lookups need one
+static ARTIST_MARKET: Market = Market::Country(Country::UnitedStates);
+const MAX_TRACKS_PER_REQUEST: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TrackData {
     pub title: String,
-    pub artists: Vec<String>
+    pub artists: Vec<String>,
+    pub disc_number: Option<i32>,
+    pub track_number: Option<u32>,
+    pub isrc: Option<String>,
+    pub duration: Option<Duration>
 }
 
 impl TrackData {
     pub fn new(title: String, artists: Vec<String>) -> Self {
-        Self { title, artists }
+        Self { title, artists, disc_number: None, track_number: None, isrc: None, duration: None }
+    }
+
+    pub fn new_album_track(title: String, artists: Vec<String>, disc_number: i32, track_number: u32) -> Self {
+        Self { title, artists, disc_number: Some(disc_number), track_number: Some(track_number), isrc: None, duration: None }
+    }
+
+    pub fn from_full_track(track: FullTrack) -> Self {
+        let mut track_data = Self::new(track.name, track.artists.into_iter().map(|artist| artist.name).collect());
+        track_data.isrc = track.external_ids.get("isrc").cloned();
+        track_data.duration = track.duration.to_std().ok();
+        track_data
+    }
+
+    pub fn query(&self) -> String {
+        format!("{} by {}", self.title, self.artists.join(", "))
     }
 }
 
 pub async fn auth() -> Result<ClientCredsSpotify, Error> {
     let creds = Credentials::from_env().unwrap();
-    OAuth::from_env(scopes!("playlist-read-private","playlist-read-collaborative","user-read-private","user-library-read")).unwrap();
     let mut spotify = ClientCredsSpotify::new(creds);
     spotify.request_token()?;
     spotify.config.token_refreshing = true;
     return Ok(spotify);
 }
 
-pub async fn extract_track_query(spotify: &ClientCredsSpotify, id: &str) -> Result<TrackData, Error> {
-    let track_id = TrackId::from_id(id)?;
-
-    let track = spotify.track(track_id)?;
-    let title = track.name.to_owned();
-    let mut artists = Vec::new();
+/// Number of tracks resolved so far, sent while long playlists page in
+pub type ResolveProgress = Arc<watch::Sender<usize>>;
+
+/// The ureq client blocks, so every request runs on tokio's blocking pool instead of a worker thread
+async fn run_blocking<C, T, F>(spotify: &C, request: F) -> Result<T, Error>
+where
+    C: BaseClient + Clone + Send + 'static,
+    T: Send + 'static,
+    F: FnOnce(C) -> Result<T, Error> + Send + 'static
+{
+    let spotify = spotify.clone();
+    spawn_blocking(move || request(spotify)).await?
+}
 
-    for artist in track.artists {
-        artists.push(artist.name);
+fn report_progress(progress: &Option<ResolveProgress>, resolved: usize) {
+    if let Some(progress) = progress {
+        progress.send_replace(resolved);
     }
+}
 
-    Ok(TrackData::new(title, artists))
+pub async fn extract_track_query(spotify: &ClientCredsSpotify, id: &str) -> Result<TrackData, Error> {
+    let track_id = TrackId::from_id(id)?.into_static();
+
+    run_blocking(spotify, move |spotify| {
+        let track = spotify.track(track_id)?;
+
+        Ok(TrackData::from_full_track(track))
+    }).await
 }
 
-pub async fn extract_playlist_queries(spotify: &ClientCredsSpotify, id: &str) -> Result<Vec<TrackData>, Error> {
-    let playlist_id = PlaylistId::from_id(id)?;
-    let mut queries: Vec<TrackData> = Vec::new();
-    let mut playlist = spotify.playlist_items(playlist_id, None, None);
+/// Takes any client, so private playlists can be read with the account of the user who queued them
+pub async fn extract_playlist_queries<C: BaseClient + Clone + Send + 'static>(spotify: &C, id: &str, progress: Option<ResolveProgress>) -> Result<Vec<TrackData>, Error> {
+    let playlist_id = PlaylistId::from_id(id)?.into_static();
 
-    while let Some(item) = playlist.next() {
-        if let Some(track) = item?.track {
-            if let PlayableItem::Track(track) = track {
-                let title = track.name;
-                let mut artists = Vec::new();
+    run_blocking(spotify, move |spotify| {
+        let mut queries: Vec<TrackData> = Vec::new();
+        let mut playlist = spotify.playlist_items(playlist_id, None, None);
 
-                for artist in track.artists {
-                    artists.push(artist.name);
+        while let Some(item) = playlist.next() {
+            if let Some(track) = item?.track {
+                if let PlayableItem::Track(track) = track {
+                    queries.push(TrackData::from_full_track(track));
+                    report_progress(&progress, queries.len());
                 }
-                
-                queries.push(TrackData::new(title, artists));
             }
         }
-    }
 
-    Ok(queries)
+        Ok(queries)
+    }).await
 }
 
-pub async fn extract_album_queries(spotify: &ClientCredsSpotify, id: &str) -> Result<Vec<TrackData>, Error> {
-    let album_id = AlbumId::from_id(id)?;
+pub async fn extract_liked_queries(spotify: &AuthCodePkceSpotify, progress: Option<ResolveProgress>) -> Result<Vec<TrackData>, Error> {
+    run_blocking(spotify, move |spotify| {
+        let mut queries: Vec<TrackData> = Vec::new();
+        let liked = spotify.current_user_saved_tracks(None);
+
+        for saved_track in liked {
+            queries.push(TrackData::from_full_track(saved_track?.track));
+            report_progress(&progress, queries.len());
+        }
+
+        Ok(queries)
+    }).await
+}
+
+pub async fn extract_album_queries(spotify: &ClientCredsSpotify, id: &str, progress: Option<ResolveProgress>) -> Result<Vec<TrackData>, Error> {
+    let album_id = AlbumId::from_id(id)?.into_static();
+
+    run_blocking(spotify, move |spotify| album_queries(&spotify, album_id, progress)).await
+}
+
+fn album_queries(spotify: &ClientCredsSpotify, album_id: AlbumId, progress: Option<ResolveProgress>) -> Result<Vec<TrackData>, Error> {
     let mut queries: Vec<TrackData> = Vec::new();
-    let mut album = spotify.album_track(album_id);
+    let mut track_ids: Vec<(usize, TrackId)> = Vec::new();
+    let album = spotify.album_track(album_id);
 
-    while let Some(track) = album.next() {
+    for track in album {
         let track = track?;
         let title = track.name;
         let mut artists = Vec::new();
@@ -77,8 +135,62 @@ pub async fn extract_album_queries(spotify: &ClientCredsSpotify, id: &str) -> Re
             artists.push(artist.name);
         }
 
-        queries.push(TrackData::new(title, artists));
+        let mut track_data = TrackData::new_album_track(title, artists, track.disc_number, track.track_number);
+        track_data.duration = track.duration.to_std().ok();
+        if let Some(track_id) = track.id {
+            track_ids.push((queries.len(), track_id));
+        }
+        queries.push(track_data);
+        report_progress(&progress, queries.len());
+    }
+
+    // Album tracks come without external ids, only full tracks carry the ISRC
+    for chunk in track_ids.chunks(MAX_TRACKS_PER_REQUEST) {
+        let tracks = spotify.tracks(chunk.iter().map(|(_, track_id)| track_id.clone()), None)?;
+        for ((index, _), track) in chunk.iter().zip(tracks) {
+            queries[*index].isrc = track.external_ids.get("isrc").cloned();
+        }
     }
 
     Ok(queries)
-}
\ No newline at end of file
+}
+
+pub async fn extract_artist_top_track_queries(spotify: &ClientCredsSpotify, id: &str) -> Result<Vec<TrackData>, Error> {
+    let artist_id = ArtistId::from_id(id)?.into_static();
+
+    run_blocking(spotify, move |spotify| {
+        let mut queries: Vec<TrackData> = Vec::new();
+
+        for track in spotify.artist_top_tracks(artist_id, ARTIST_MARKET)? {
+            queries.push(TrackData::from_full_track(track));
+        }
+
+        Ok(queries)
+    }).await
+}
+
+pub async fn extract_artist_latest_album_queries(spotify: &ClientCredsSpotify, id: &str, progress: Option<ResolveProgress>) -> Result<Vec<TrackData>, Error> {
+    let artist_id = ArtistId::from_id(id)?.into_static();
+    let artist_url = format!("https://open.spotify.com/artist/{}", id);
+
+    run_blocking(spotify, move |spotify| {
+        let mut latest_album: Option<(String, AlbumId)> = None;
+        let mut albums = spotify.artist_albums(artist_id, Some(AlbumType::Album), Some(ARTIST_MARKET));
+
+        for album in albums.by_ref() {
+            let album = album?;
+            // Release dates are `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, which still compare in order as strings
+            if let (Some(release_date), Some(album_id)) = (album.release_date, album.id) {
+                if latest_album.as_ref().is_none_or(|(latest_release_date, _)| release_date > *latest_release_date) {
+                    latest_album = Some((release_date, album_id));
+                }
+            }
+        }
+        drop(albums);
+
+        match latest_album {
+            Some((_, album_id)) => album_queries(&spotify, album_id, progress),
+            None => Err(query_error!(NoSearchResults, query: artist_url).into())
+        }
+    }).await
+}
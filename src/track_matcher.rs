@@ -0,0 +1,292 @@
+use std::{ process::Stdio, sync::Arc, time::Duration };
+use songbird::input::Metadata;
+use tokio::{ process::Command, sync::OnceCell };
+use crate::{ error::Error, spotify_to_query::TrackData, youtube_scraper::{ search, SearchResult } };
+
+const SEARCH_CANDIDATES: u32 = 5;
+/// Versions nobody asks for unless the Spotify title asks for them too
+static PENALTY_WORDS: [&str; 17] = [
+    "live", "cover", "remix", "karaoke", "instrumental", "acoustic", "nightcore", "reaction", "sped up",
+    "slowed", "reverb", "8d", "1 hour", "10 hours", "loop", "tutorial", "extended"
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub title: String,
+    pub channel: String,
+    pub duration: Option<Duration>,
+    pub url: String,
+    /// Found by searching for the ISRC rather than the title
+    pub from_isrc: bool
+}
+
+impl Candidate {
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            title: Some(self.title.clone()),
+            channel: Some(self.channel.clone()),
+            duration: Some(self.duration.unwrap_or(Duration::ZERO)),
+            source_url: Some(self.url.clone()),
+            channels: Some(2),
+            sample_rate: Some(48000),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<SearchResult> for Candidate {
+    fn from(result: SearchResult) -> Self {
+        Self { url: result.url(), title: result.title, channel: result.channel.unwrap_or_default(), duration: result.duration, from_isrc: false }
+    }
+}
+
+/// The video a Spotify track was matched to, shared by everything that plays or shows the track.
+/// Searches don't return the same results every time, so the match is only ever made once
+#[derive(Debug, Clone, Default)]
+pub struct MatchedCandidate(Arc<OnceCell<Option<Candidate>>>);
+
+impl MatchedCandidate {
+    /// Matches the track on the first call, falling back to the top search result when matching fails
+    pub async fn get(&self, track_data: &TrackData) -> Option<Candidate> {
+        self.0.get_or_init(|| async {
+            match match_track(track_data).await {
+                Ok(Some(candidate)) => Some(candidate),
+                _ => search(&track_data.query()).await.ok().and_then(|results| results.into_iter().next()).map(Candidate::from)
+            }
+        }).await.clone()
+    }
+}
+
+impl PartialEq for MatchedCandidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.get() == other.0.get()
+    }
+}
+
+/// Searches YouTube by ISRC and by title and picks the candidate closest to the Spotify track
+pub async fn match_track(track_data: &TrackData) -> Result<Option<Candidate>, Error> {
+    let query = track_data.query();
+    let (isrc_candidates, query_candidates) = tokio::join!(
+        async {
+            match track_data.isrc {
+                Some(ref isrc) => ytdl_search_candidates(&format!("\"{}\"", isrc), true).await,
+                None => Ok(vec![])
+            }
+        },
+        ytdl_search_candidates(&query, false)
+    );
+
+    let mut candidates = isrc_candidates.unwrap_or_default();
+    for candidate in query_candidates? {
+        if !candidates.iter().any(|isrc_candidate| isrc_candidate.url == candidate.url) {
+            candidates.push(candidate);
+        }
+    }
+
+    Ok(best_candidate(track_data, &candidates).cloned())
+}
+
+pub fn best_candidate<'a>(track_data: &TrackData, candidates: &'a [Candidate]) -> Option<&'a Candidate> {
+    candidates.iter()
+        .map(|candidate| (candidate, score_candidate(track_data, candidate)))
+        .fold(None, |best: Option<(&Candidate, f64)>, (candidate, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((candidate, score))
+        })
+        .map(|(candidate, _)| candidate)
+}
+
+pub fn score_candidate(track_data: &TrackData, candidate: &Candidate) -> f64 {
+    let mut score = 0.0;
+    let track_title = normalize(&track_data.title);
+    let candidate_title = normalize(&candidate.title);
+    let candidate_channel = normalize(&candidate.channel);
+
+    score += 40.0 * title_similarity(&track_title, &candidate_title);
+    let has_artist = track_data.artists.iter()
+        .map(|artist| normalize(artist))
+        .any(|artist| !artist.is_empty() && (contains_phrase(&candidate_title, &artist) || contains_phrase(&candidate_channel, &artist)));
+    if has_artist {
+        score += 15.0;
+    }
+
+    // Auto generated "Artist - Topic" uploads are the plain studio recording
+    if candidate.channel.ends_with(" - Topic") {
+        score += 15.0;
+    }
+    if contains_phrase(&candidate_title, "official audio") {
+        score += 10.0;
+    } else if contains_phrase(&candidate_title, "official video") || contains_phrase(&candidate_title, "official music video") {
+        score += 5.0;
+    }
+    if contains_phrase(&candidate_title, "lyrics") || contains_phrase(&candidate_title, "lyric video") {
+        score -= 5.0;
+    }
+    for word in PENALTY_WORDS {
+        if contains_phrase(&candidate_title, word) && !contains_phrase(&track_title, word) {
+            score -= 25.0;
+        }
+    }
+
+    match (track_data.duration.filter(|duration| !duration.is_zero()), candidate.duration) {
+        (Some(duration), Some(candidate_duration)) => {
+            let delta = duration.as_secs_f64() - candidate_duration.as_secs_f64();
+            if delta.abs() <= 3.0 {
+                score += 25.0;
+            } else {
+                score -= delta.abs().min(120.0) / 2.0;
+            }
+        },
+        // Without a duration it's a livestream
+        (_, None) => score -= 30.0,
+        _ => {}
+    }
+
+    if candidate.from_isrc {
+        score += 20.0;
+    }
+    score
+}
+
+/// Share of the words in either title that both titles have
+fn title_similarity(track_title: &str, candidate_title: &str) -> f64 {
+    let track_words: Vec<&str> = track_title.split_whitespace().collect();
+    let candidate_words: Vec<&str> = candidate_title.split_whitespace().collect();
+    if track_words.is_empty() || candidate_words.is_empty() {
+        return 0.0;
+    }
+
+    let shared_words = track_words.iter().filter(|word| candidate_words.contains(word)).count() as f64;
+    // Candidate titles also carry the artist and tags, so missing track words weigh more than extra ones
+    0.75 * shared_words / track_words.len() as f64 + 0.25 * shared_words / candidate_words.len() as f64
+}
+
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|character| if character.is_alphanumeric() { character } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn contains_phrase(text: &str, phrase: &str) -> bool {
+    format!(" {} ", text).contains(&format!(" {} ", phrase))
+}
+
+async fn ytdl_search_candidates(query: &str, from_isrc: bool) -> Result<Vec<Candidate>, Error> {
+    let mut cmd = Command::new("yt-dlp");
+    cmd.args::<Vec<&str>, &str>(vec![
+        "-j", "--simulate", "--flat-playlist", "-R", "infinite", "--ignore-config", "--no-warnings", &format!("ytsearch{}:{}", SEARCH_CANDIDATES, query)
+    ]);
+    let out = cmd.stdin(Stdio::null()).output().await?;
+
+    let mut candidates: Vec<Candidate> = vec![];
+    for line in out.stdout.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()) {
+        let value = serde_json::from_slice::<serde_json::Value>(line)?;
+        let string_field = |field: &str| value.get(field).and_then(serde_json::Value::as_str).map(|field| field.to_owned());
+        if let (Some(title), Some(url)) = (string_field("title"), string_field("url")) {
+            candidates.push(Candidate {
+                title,
+                channel: string_field("channel").or(string_field("uploader")).unwrap_or_default(),
+                duration: value.get("duration").and_then(serde_json::Value::as_f64).map(Duration::from_secs_f64),
+                url,
+                from_isrc
+            });
+        }
+    }
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(title: &str, channel: &str, seconds: Option<u64>) -> Candidate {
+        Candidate { title: title.to_owned(), channel: channel.to_owned(), duration: seconds.map(Duration::from_secs), url: format!("https://www.youtube.com/watch?v={}", title.len()), from_isrc: false }
+    }
+
+    fn track(title: &str, artist: &str, seconds: u64) -> TrackData {
+        let mut track_data = TrackData::new(title.to_owned(), vec![artist.to_owned()]);
+        track_data.duration = Some(Duration::from_secs(seconds));
+        track_data
+    }
+
+    #[test]
+    fn prefers_the_studio_recording() {
+        let track_data = track("Blinding Lights", "The Weeknd", 200);
+        let candidates = vec![
+            candidate("The Weeknd - Blinding Lights (Live at the Super Bowl)", "The Weeknd", Some(215)),
+            candidate("The Weeknd - Blinding Lights (Lyrics)", "Lyric Vibes", Some(262)),
+            candidate("Blinding Lights 1 hour loop", "Loops", Some(3600)),
+            candidate("Blinding Lights", "The Weeknd - Topic", Some(201)),
+            candidate("The Weeknd - Blinding Lights (Official Video)", "TheWeekndVEVO", Some(262)),
+        ];
+
+        assert_eq!(best_candidate(&track_data, &candidates).unwrap().channel, "The Weeknd - Topic");
+    }
+
+    #[test]
+    fn penalizes_covers_and_remixes() {
+        let track_data = track("Hallelujah", "Jeff Buckley", 414);
+        let candidates = vec![
+            candidate("Hallelujah - Jeff Buckley (cover)", "Some Singer", Some(410)),
+            candidate("Jeff Buckley - Hallelujah (Nightcore Remix)", "Nightcore Hub", Some(300)),
+            candidate("Jeff Buckley - Hallelujah (Official Audio)", "Jeff Buckley", Some(415)),
+        ];
+
+        assert_eq!(best_candidate(&track_data, &candidates).unwrap().title, "Jeff Buckley - Hallelujah (Official Audio)");
+    }
+
+    #[test]
+    fn keeps_versions_the_track_asks_for() {
+        let track_data = track("Hotel California - Live On MTV, 1994", "Eagles", 427);
+        let candidates = vec![
+            candidate("Eagles - Hotel California (Official Audio)", "Eagles", Some(391)),
+            candidate("Eagles - Hotel California (Live On MTV 1994)", "Eagles", Some(428)),
+        ];
+
+        assert_eq!(best_candidate(&track_data, &candidates).unwrap().title, "Eagles - Hotel California (Live On MTV 1994)");
+    }
+
+    #[test]
+    fn trusts_isrc_matches_and_skips_livestreams() {
+        let track_data = track("Midnight City", "M83", 244);
+        let mut isrc_candidate = candidate("Midnight City", "M83 - Topic", Some(244));
+        isrc_candidate.from_isrc = true;
+        let candidates = vec![
+            candidate("M83 - Midnight City 24/7 radio", "Chill Radio", None),
+            candidate("M83 - Midnight City", "M83", Some(244)),
+            isrc_candidate.clone(),
+        ];
+
+        assert_eq!(best_candidate(&track_data, &candidates), Some(&isrc_candidate));
+        assert!(score_candidate(&track_data, &candidates[0]) < score_candidate(&track_data, &candidates[1]));
+    }
+
+    #[tokio::test]
+    async fn clones_share_the_match() {
+        let track_data = track("Midnight City", "M83", 244);
+        let matched = MatchedCandidate::default();
+        let queued_copy = matched.clone();
+        matched.0.set(Some(candidate("M83 - Midnight City", "M83", Some(244)))).unwrap();
+
+        assert_eq!(queued_copy.get(&track_data).await.map(|candidate| candidate.channel), Some("M83".to_owned()));
+        assert_eq!(queued_copy, matched);
+        assert_ne!(MatchedCandidate::default(), matched);
+    }
+
+    #[test]
+    fn handles_missing_durations_and_candidates() {
+        let mut track_data = track("Around the World", "Daft Punk", 0);
+        track_data.duration = None;
+        let candidates = vec![
+            candidate("Red Hot Chili Peppers - Around the World", "RHCP", Some(239)),
+            candidate("Daft Punk - Around The World (Official Audio)", "Daft Punk", Some(429)),
+        ];
+
+        assert_eq!(best_candidate(&track_data, &candidates).unwrap().channel, "Daft Punk");
+        assert_eq!(best_candidate(&track_data, &[]), None);
+    }
+}
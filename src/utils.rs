@@ -1,7 +1,9 @@
-use crate::{Context, MiniMetadata, MiniUser};
-use poise::serenity_prelude::CreateEmbed;
+use crate::{Context, MiniMetadata, MiniUser, LazyMetadataTrait, MetadataEventHandler, loop_mode::LoopEventHandler, history::HistoryEventHandler, youtube_scraper::SearchResult, error::{Error, QueryError}};
+use poise::serenity_prelude::{CreateEmbed, ChannelId, GuildId};
 use serenity::utils::Color;
-use std::time::Duration;
+use songbird::{Call, tracks::TrackHandle, events::{Event, TrackEvent}};
+use tokio::sync::Mutex;
+use std::{time::Duration, sync::Arc};
 
 pub async fn send_error(ctx: &Context<'_>, description: &str) {
     let result = ctx.send(|message| message
@@ -27,6 +29,59 @@ pub async fn send_reply(ctx: &Context<'_>, description: &str) {
     }
 }
 
+/// The guild's call, joining the channel first with now playing messages, loop modes and history set up
+pub async fn join_call(ctx: &Context<'_>, guild_id: GuildId, channel_id: ChannelId) -> Arc<Mutex<Call>> {
+    let manager = songbird::get(ctx.serenity_context()).await.unwrap();
+    if let Some(handler) = manager.get(guild_id) {
+        return handler;
+    }
+
+    let handler = manager.join(guild_id, channel_id).await.0;
+    let mut handler_guard = handler.lock().await;
+    handler_guard.add_global_event(Event::Track(TrackEvent::Play), MetadataEventHandler {handler: handler.clone(), channel_id: ctx.channel_id(), http: ctx.serenity_context().http.clone()});
+    for event in [TrackEvent::Play, TrackEvent::End] {
+        handler_guard.add_global_event(Event::Track(event), LoopEventHandler {
+            guild_id: guild_id.0,
+            handler: handler.clone(),
+            loop_modes: ctx.data().loop_modes.clone(),
+            channel_id: ctx.channel_id(),
+            http: ctx.serenity_context().http.clone()
+        });
+    }
+    handler_guard.add_global_event(Event::Track(TrackEvent::End), HistoryEventHandler {guild_id: guild_id.0, history: ctx.data().history.clone()});
+    drop(handler_guard);
+    handler
+}
+
+/// The call the author is in with the bot, telling them when they're in a different channel
+pub async fn author_call(ctx: &Context<'_>) -> Option<Arc<Mutex<Call>>> {
+    let guild = ctx.guild()?;
+    let user_voice_state = guild.voice_states.get(&ctx.author().id)?;
+    let manager = songbird::get(ctx.serenity_context()).await.unwrap();
+    let handler = manager.get(guild.id)?;
+    if user_voice_state.channel_id.map(|f| f.0) != handler.lock().await.current_channel().map(|f| f.0) {
+        send_error(ctx, "You're In a Different Channel").await;
+        return None;
+    }
+    Some(handler)
+}
+
+/// Lazy tracks that haven't been looked up yet go by their search query
+pub async fn track_title(track: &TrackHandle) -> String {
+    match track.read_lazy_metadata().await {
+        Some(metadata) => format_title(&metadata),
+        None if track.is_lazy() => track.metadata().title.clone().unwrap_or_default(),
+        None => format_title(&MiniMetadata::lossy_from_metadata(track.metadata().clone()))
+    }
+}
+
+pub fn describe_query_error(error: &Error) -> String {
+    match error.downcast_ref::<QueryError>() {
+        Some(query_error) => query_error.user_description(),
+        None => "Invalid Query".to_owned()
+    }
+}
+
 pub fn format_duration(duration: Duration, length: Option<u32>) -> String {
     let s = duration.as_secs() % 60;
     let m = duration.as_secs() / 60 % 60;
@@ -45,13 +100,48 @@ pub fn format_duration(duration: Duration, length: Option<u32>) -> String {
     formatted_duration
 }
 
+/// Cuts text down to Discord's limits for things like select menu options and autocomplete choices
+pub fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_owned();
+    }
+    text.chars().take(max_length.saturating_sub(3)).collect::<String>() + "..."
+}
+
+/// Search results without a length are livestreams
+pub fn format_result_duration(result: &SearchResult) -> String {
+    match result.duration {
+        Some(duration) => format_duration(duration, None),
+        None => "LIVE".to_owned()
+    }
+}
+
+/// Local files have no link to point at, so only their title is shown
+pub fn format_title(metadata: &MiniMetadata) -> String {
+    if metadata.source_url.starts_with("http") {
+        return format!("[{}]({})", metadata.title, metadata.source_url);
+    }
+    metadata.title.clone()
+}
+
+/// Live streams have no meaningful duration, so they're shown as `LIVE`
+pub fn format_metadata_duration(metadata: &MiniMetadata) -> String {
+    if metadata.is_live {
+        return "LIVE".to_owned();
+    }
+    format_duration(metadata.duration, None)
+}
+
 pub fn create_now_playing_embed(metadata: MiniMetadata, added_by: Option<MiniUser>) -> CreateEmbed {
-    let formatted_duration = format_duration(metadata.duration, None);
+    let formatted_duration = format_metadata_duration(&metadata);
     let mut embed = CreateEmbed::default();
     embed
     .title("Now Playing:")
-    .description(format!("[{}]({}) | {}", metadata.title, metadata.source_url, formatted_duration))
+    .description(format!("{} | {}", format_title(&metadata), formatted_duration))
     .color(Color::PURPLE);
+    if let (Some(disc_number), Some(track_number)) = (metadata.disc_number, metadata.track_number) {
+        embed.footer(|footer| footer.text(format!("Disc {} | Track {}", disc_number, track_number)));
+    }
     if let Some(added_by) = added_by {
         embed.author(|author| { author.url(format!("https://discordapp.com/users/{}", added_by.id)).name(added_by.name);
             if let Some(avatar_url) = added_by.avatar_url {
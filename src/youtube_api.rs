@@ -1,12 +1,16 @@
 use crate::{
     missing_value,
-    error::Error
+    error::Error,
+    youtube_quota::{ Endpoint, QuotaMeter }
 };
 use songbird::input::Metadata;
 use google_youtube3::{YouTube, hyper::client::HttpConnector, hyper_rustls::HttpsConnector, api::Video};
 use std::str::FromStr;
 
-pub async fn extract_video_metadata(youtube_client: &YouTube<HttpsConnector<HttpConnector>>, id: &str) -> Result<Metadata, Error> {
+const MAX_RESULTS_PER_PAGE: u32 = 50;
+
+pub async fn extract_video_metadata(youtube_client: &YouTube<HttpsConnector<HttpConnector>>, quota: &QuotaMeter, id: &str) -> Result<Metadata, Error> {
+    quota.spend(Endpoint::VideosList).await?;
     let (_, result) = youtube_client.videos()
         .list(&vec!["contentDetails".to_owned(), "snippet".to_owned()])
         .add_id(id)
@@ -25,39 +29,57 @@ pub async fn extract_video_metadata(youtube_client: &YouTube<HttpsConnector<Http
     }
 }
 
-pub async fn extract_playlist_video_metadata(youtube_client: &YouTube<HttpsConnector<HttpConnector>>, id: &str) -> Result<Vec<Metadata>, Error> {
-    let (_, result) = youtube_client.playlist_items()
-        .list(&vec![])
-        .playlist_id(id)
-        .max_results(50)
-        .doit().await?;
-    
-    let playlist_items = result.items;
-    match playlist_items {
-        Some(playlist_items) => {
-            let mut playlist_item_ids: Vec<String> = vec![];
-            for playlist_item in playlist_items {
-                playlist_item_ids.push(playlist_item.id.ok_or(missing_value!("playlist_item_id"))?);
-            }
+pub async fn extract_playlist_video_metadata(youtube_client: &YouTube<HttpsConnector<HttpConnector>>, quota: &QuotaMeter, id: &str) -> Result<Vec<Metadata>, Error> {
+    let video_ids = extract_playlist_video_ids(youtube_client, quota, id).await?;
 
-            let (_, result) = youtube_client.videos()
+    let mut metadata_collector: Vec<Metadata> = vec![];
+    for video_ids_chunk in video_ids.chunks(MAX_RESULTS_PER_PAGE as usize) {
+        quota.spend(Endpoint::VideosList).await?;
+        let (_, result) = youtube_client.videos()
             .list(&vec!["contentDetails".to_owned(), "snippet".to_owned()])
-            .add_id(&playlist_item_ids.join(","))
+            .add_id(&video_ids_chunk.join(","))
             .doit().await?;
-            
-            match result.items {
-                Some(video_items) => {
-                    let mut metadata_collector: Vec<Metadata> = vec![];
-                    for video in video_items {
-                        metadata_collector.push(extract_metadata(&video)?);
-                    }
-                    return Ok(metadata_collector);
-                },
-                None => {return Err(missing_value!("video_items").into());}
+
+        let video_items = result.items.ok_or(missing_value!("video_items"))?;
+        // The videos endpoint doesn't preserve the requested order and silently omits private or deleted videos
+        for video_id in video_ids_chunk {
+            if let Some(video) = video_items.iter().find(|video| video.id.as_ref() == Some(video_id)) {
+                metadata_collector.push(extract_metadata(video)?);
             }
-        },
-        None => {return Err(missing_value!("playlist_items").into())}
+        }
     }
+
+    Ok(metadata_collector)
+}
+
+async fn extract_playlist_video_ids(youtube_client: &YouTube<HttpsConnector<HttpConnector>>, quota: &QuotaMeter, id: &str) -> Result<Vec<String>, Error> {
+    let mut video_ids: Vec<String> = vec![];
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut request = youtube_client.playlist_items()
+            .list(&vec!["contentDetails".to_owned()])
+            .playlist_id(id)
+            .max_results(MAX_RESULTS_PER_PAGE);
+        if let Some(ref page_token) = page_token {
+            request = request.page_token(page_token);
+        }
+        quota.spend(Endpoint::PlaylistItemsList).await?;
+        let (_, result) = request.doit().await?;
+
+        let playlist_items = result.items.ok_or(missing_value!("playlist_items"))?;
+        for playlist_item in playlist_items {
+            let content_details = playlist_item.content_details.ok_or(missing_value!("contentDetails"))?;
+            video_ids.push(content_details.video_id.ok_or(missing_value!("videoId"))?);
+        }
+
+        page_token = result.next_page_token;
+        if page_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(video_ids)
 }
 
 fn extract_metadata(video: &Video) -> Result<Metadata, Error> {
@@ -0,0 +1,204 @@
+use std::{ collections::BTreeMap, fmt::Display, path::PathBuf, time::{ SystemTime, UNIX_EPOCH } };
+use serde::{ Deserialize, Serialize };
+use tokio::sync::Mutex;
+use crate::{ quota_error, error::Error };
+
+pub const DAILY_LIMIT: u32 = 10000;
+pub const DEFAULT_THRESHOLD: u32 = 9000;
+/// The quota resets at midnight Pacific time, daylight saving time is ignored
+const RESET_OFFSET_SECS: u64 = 8 * 3600;
+
+/// Data API endpoints the bot calls
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Endpoint {
+    VideosList,
+    PlaylistItemsList
+}
+
+impl Endpoint {
+    /// Units a single call costs, regardless of how many parts or ids it asks for
+    pub fn cost(&self) -> u32 {
+        match self {
+            Self::VideosList => 1,
+            Self::PlaylistItemsList => 1
+        }
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VideosList => f.write_str("videos.list"),
+            Self::PlaylistItemsList => f.write_str("playlistItems.list")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// Days since the epoch in Pacific time
+    pub day: u64,
+    pub units: u32,
+    pub calls: BTreeMap<String, u32>
+}
+
+/// Keeps the day's Data API usage on disk, refusing calls once it reaches the threshold
+pub struct QuotaMeter {
+    path: PathBuf,
+    pub limit: u32,
+    pub threshold: u32,
+    usage: Mutex<QuotaUsage>
+}
+
+impl QuotaMeter {
+    /// A missing or unreadable usage file starts the count over rather than keeping the bot from starting
+    pub fn new(path: PathBuf, limit: u32, threshold: u32) -> Self {
+        let usage: QuotaUsage = match std::fs::read(&path).map(|content| serde_json::from_slice(&content)) {
+            Ok(Ok(usage)) => usage,
+            Ok(Err(err)) => {
+                println!("Couldn't read the YouTube quota usage, starting over: {}", err);
+                QuotaUsage::default()
+            },
+            Err(_) => QuotaUsage::default()
+        };
+        Self { path, limit, threshold: threshold.min(limit), usage: Mutex::new(usage) }
+    }
+
+    /// Reads `YOUTUBE_QUOTA_LIMIT` and `YOUTUBE_QUOTA_THRESHOLD`, falling back to the default quota
+    pub fn from_env(path: PathBuf) -> Self {
+        let env_units = |name: &str| std::env::var(name).ok().and_then(|units| units.parse::<u32>().ok());
+        let limit = env_units("YOUTUBE_QUOTA_LIMIT").unwrap_or(DAILY_LIMIT);
+        let threshold = env_units("YOUTUBE_QUOTA_THRESHOLD").unwrap_or(DEFAULT_THRESHOLD * limit / DAILY_LIMIT);
+        Self::new(path, limit, threshold)
+    }
+
+    /// Records a call before it's made, erroring instead when it would go over the threshold
+    pub async fn spend(&self, endpoint: Endpoint) -> Result<(), Error> {
+        let mut usage = self.usage.lock().await;
+        roll_over(&mut usage, today());
+        if usage.units + endpoint.cost() > self.threshold {
+            return Err(quota_error!(BudgetReached, used: usage.units, threshold: self.threshold).into());
+        }
+
+        usage.units += endpoint.cost();
+        *usage.calls.entry(endpoint.to_string()).or_insert(0) += 1;
+        self.save(&usage).await
+    }
+
+    /// YouTube refused a call over quota, so nothing more is spent until the reset
+    pub async fn exhaust(&self) -> Result<(), Error> {
+        let mut usage = self.usage.lock().await;
+        roll_over(&mut usage, today());
+        usage.units = usage.units.max(self.limit);
+        self.save(&usage).await
+    }
+
+    pub async fn usage(&self) -> QuotaUsage {
+        let mut usage = self.usage.lock().await;
+        roll_over(&mut usage, today());
+        usage.clone()
+    }
+
+    /// Called with the usage still locked, so writes land in the order they were made
+    async fn save(&self, usage: &QuotaUsage) -> Result<(), Error> {
+        tokio::fs::write(&self.path, serde_json::to_vec(usage)?).await?;
+        Ok(())
+    }
+}
+
+/// Seconds until the quota resets
+pub fn seconds_until_reset() -> u64 {
+    86400 - pacific_secs() % 86400
+}
+
+/// Whether the Data API itself refused a call for going over quota
+pub fn is_quota_exceeded(error: &Error) -> bool {
+    match error.downcast_ref::<google_youtube3::Error>() {
+        Some(google_youtube3::Error::BadRequest(value)) => value.pointer("/error/errors/0/reason")
+            .and_then(serde_json::Value::as_str)
+            .is_some_and(|reason| reason == "quotaExceeded" || reason == "dailyLimitExceeded"),
+        _ => false
+    }
+}
+
+fn pacific_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs()).saturating_sub(RESET_OFFSET_SECS)
+}
+
+fn today() -> u64 {
+    pacific_secs() / 86400
+}
+
+fn roll_over(usage: &mut QuotaUsage, day: u64) {
+    if usage.day != day {
+        *usage = QuotaUsage { day, ..QuotaUsage::default() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meter(name: &str, threshold: u32) -> QuotaMeter {
+        let path = std::env::temp_dir().join(format!("diwa_quota_{}_{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        QuotaMeter::new(path, 10, threshold)
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_threshold_and_persists() {
+        let meter = meter("threshold", 3);
+        for _ in 0..3 {
+            meter.spend(Endpoint::VideosList).await.unwrap();
+        }
+        let error = meter.spend(Endpoint::PlaylistItemsList).await.unwrap_err();
+        assert!(error.downcast_ref::<crate::error::QuotaError>().is_some());
+
+        let reloaded = QuotaMeter::new(meter.path.clone(), 10, 3);
+        let usage = reloaded.usage().await;
+        assert_eq!(usage.units, 3);
+        assert_eq!(usage.calls.get("videos.list"), Some(&3));
+        assert_eq!(usage.calls.get("playlistItems.list"), None);
+        let _ = std::fs::remove_file(&meter.path);
+    }
+
+    #[tokio::test]
+    async fn resets_on_a_new_day() {
+        let meter = meter("reset", 5);
+        meter.exhaust().await.unwrap();
+        assert_eq!(meter.usage().await.units, 10);
+        assert!(meter.spend(Endpoint::VideosList).await.is_err());
+
+        let mut usage = meter.usage().await;
+        let tomorrow = usage.day + 1;
+        roll_over(&mut usage, tomorrow);
+        assert_eq!((usage.units, usage.calls.len()), (0, 0));
+        let _ = std::fs::remove_file(&meter.path);
+    }
+
+    #[tokio::test]
+    async fn starts_over_from_a_corrupt_file() {
+        let meter = meter("corrupt", 5);
+        std::fs::write(&meter.path, b"{\"day\": 1, \"uni").unwrap();
+
+        let reloaded = QuotaMeter::new(meter.path.clone(), 10, 5);
+        assert_eq!(reloaded.usage().await.units, 0);
+        reloaded.spend(Endpoint::VideosList).await.unwrap();
+        assert_eq!(QuotaMeter::new(meter.path.clone(), 10, 5).usage().await.units, 1);
+        let _ = std::fs::remove_file(&meter.path);
+    }
+
+    #[test]
+    fn recognizes_quota_errors() {
+        let error: Error = google_youtube3::Error::BadRequest(serde_json::json!({
+            "error": {"code": 403, "errors": [{"reason": "quotaExceeded", "domain": "youtube.quota"}]}
+        })).into();
+        assert!(is_quota_exceeded(&error));
+        assert!(!crate::retry::is_transient(&error));
+
+        let error: Error = google_youtube3::Error::BadRequest(serde_json::json!({
+            "error": {"code": 404, "errors": [{"reason": "videoNotFound"}]}
+        })).into();
+        assert!(!is_quota_exceeded(&error));
+    }
+}
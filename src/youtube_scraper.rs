@@ -1,126 +1,365 @@
 use std::time::Duration;
 use reqwest::Client;
-use scraper::{ html::Html, selector::Selector };
-use nom::{ IResult, bytes::complete::take_until };
-use crate::MiniMetadata;
-
-fn parse_quotes(input: &str) -> IResult<&str, &str> {
-    let res = take_until("\"")(input);
-    match res {
-        Ok((remainder, _)) => { 
-            take_until("\"")(remainder.split_at(1).1)
-        },
-        e @ Err(_) => e
-    }
+use serde::{ de::DeserializeOwned, Deserialize };
+use songbird::input::Metadata;
+use crate::{ missing_value, error::Error };
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub video_id: String,
+    pub title: String,
+    pub channel: Option<String>,
+    /// `None` for livestreams
+    pub duration: Option<Duration>
 }
 
-fn parse_bracket_quotes(input: &str) -> IResult<&str, &str> {
-    let res = take_until("\"")(input);
-    match res {
-        Ok((remainder, _)) => { 
-            take_until("\"}")(remainder.split_at(1).1)
-        },
-        e @ Err(_) => e
+impl SearchResult {
+    pub fn url(&self) -> String {
+        format!("https://youtu.be/{}", self.video_id)
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            title: Some(self.title.clone()),
+            channel: self.channel.clone(),
+            duration: self.duration,
+            source_url: Some(self.url()),
+            channels: Some(2),
+            sample_rate: Some(48000),
+            ..Default::default()
+        }
     }
 }
 
-fn parse_colon(input: &str) -> IResult<&str, &str> {
-    take_until(":")(input).map(|result| (result.0.split_at(1).1, result.1))
-}
-
-fn parse_title(input: &str) -> IResult<&str, String> {
-    let result: IResult<&str, &str> = take_until("\"title\":")(input);
-    match result {
-        Ok((remainder, _)) => {
-            match take_until("\"text\":")(remainder) {
-                Ok((remainder, _)) => {
-                    match parse_colon(remainder) {
-                        Ok((remainder, _)) => {
-                            parse_bracket_quotes(remainder).map(|(a, b)| (a, b.replace("\\\"", "\"")))
-                        },
-                        Err(err) => Err(err)
-                    }
-                },
-                Err(err) => Err(err)
-            }
-        },
-        Err(err) => Err(err)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitialData {
+    contents: Option<InitialDataContents>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitialDataContents {
+    two_column_search_results_renderer: Option<TwoColumnSearchResultsRenderer>,
+    two_column_browse_results_renderer: Option<TwoColumnBrowseResultsRenderer>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TwoColumnSearchResultsRenderer {
+    primary_contents: PrimaryContents
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PrimaryContents {
+    section_list_renderer: SectionListRenderer
+}
+
+/// Playlist pages put their videos in the first tab
+#[derive(Deserialize)]
+struct TwoColumnBrowseResultsRenderer {
+    #[serde(default)]
+    tabs: Vec<Tab>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Tab {
+    tab_renderer: Option<TabRenderer>
+}
+
+#[derive(Deserialize)]
+struct TabRenderer {
+    content: Option<TabContent>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TabContent {
+    section_list_renderer: Option<SectionListRenderer>
+}
+
+#[derive(Deserialize)]
+struct SectionListRenderer {
+    #[serde(default)]
+    contents: Vec<Section>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Section {
+    item_section_renderer: Option<ItemSectionRenderer>
+}
+
+#[derive(Deserialize)]
+struct ItemSectionRenderer {
+    #[serde(default)]
+    contents: Vec<SectionItem>
+}
+
+/// Only direct `videoRenderer` items are results, ads, channels and shelves of suggestions are skipped
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SectionItem {
+    video_renderer: Option<VideoRenderer>,
+    playlist_video_list_renderer: Option<PlaylistVideoListRenderer>
+}
+
+#[derive(Deserialize)]
+struct PlaylistVideoListRenderer {
+    #[serde(default)]
+    contents: Vec<PlaylistItem>
+}
+
+/// The continuation item at the end of long playlists is skipped, so only the first page of videos is read
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaylistItem {
+    playlist_video_renderer: Option<PlaylistVideoRenderer>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlaylistVideoRenderer {
+    video_id: String,
+    title: Text,
+    short_byline_text: Option<Text>,
+    length_seconds: Option<String>,
+    /// Deleted and private videos stay in the list but can't be played
+    is_playable: Option<bool>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VideoRenderer {
+    video_id: String,
+    title: Text,
+    owner_text: Option<Text>,
+    length_text: Option<Text>
+}
+
+/// YouTube sends text either whole or split into formatted runs
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Text {
+    simple_text: Option<String>,
+    #[serde(default)]
+    runs: Vec<TextRun>
+}
+
+#[derive(Deserialize)]
+struct TextRun {
+    text: String
+}
+
+impl Text {
+    fn text(self) -> String {
+        match self.simple_text {
+            Some(simple_text) => simple_text,
+            None => self.runs.into_iter().map(|run| run.text).collect()
+        }
     }
 }
 
-fn parse_video_id(input: &str) -> IResult<&str, &str> {
-    let result = take_until("\"videoId\":")(input);
-    match result {
-        Ok((remainder, _)) => {
-            match parse_colon(remainder) {
-                Ok((remainder, _)) => {
-                    parse_quotes(remainder)
-                },
-                Err(err) => Err(err)
-            }
-        },
-        Err(err) => Err(err)
+impl From<VideoRenderer> for SearchResult {
+    fn from(video_renderer: VideoRenderer) -> Self {
+        Self {
+            video_id: video_renderer.video_id,
+            title: video_renderer.title.text(),
+            channel: video_renderer.owner_text.map(Text::text),
+            // A length that can't be read still isn't a livestream
+            duration: video_renderer.length_text.map(|length_text| string_to_duration(&length_text.text()).unwrap_or(Duration::ZERO))
+        }
     }
 }
 
-fn parse_duration_text(input: &str) -> IResult<&str, &str> {
-    let result = take_until("\"lengthText\":")(input);
-    match result {
-        Ok((remainder, _)) => {
-            match take_until("\"simpleText\":")(remainder) {
-                Ok((remainder, _)) => {
-                    match parse_colon(remainder) {
-                        Ok((remainder, _)) => {
-                            parse_quotes(remainder)
-                        },
-                        Err(err) => Err(err)
-                    }
-                },
-                Err(err) => Err(err)
-            }
-        },
-        Err(err) => Err(err)
+impl From<PlaylistVideoRenderer> for SearchResult {
+    fn from(video_renderer: PlaylistVideoRenderer) -> Self {
+        Self {
+            video_id: video_renderer.video_id,
+            title: video_renderer.title.text(),
+            channel: video_renderer.short_byline_text.map(Text::text),
+            duration: video_renderer.length_seconds.and_then(|length_seconds| length_seconds.parse::<u64>().ok()).map(Duration::from_secs)
+        }
     }
 }
 
-fn string_to_duration(input: &str) -> Duration {
-    let mut time_sections = input.split(":").collect::<Vec<&str>>();
+/// `None` when the length is too long to be real
+fn string_to_duration(input: &str) -> Option<Duration> {
     let mut seconds: u64 = 0;
-    let mut multiplier = 1;
-    for time_section in time_sections.iter().rev() {
-        if let Ok(time_section) = time_section.parse::<u64>() {
-            seconds += time_section * multiplier;
+    let mut multiplier: u64 = 1;
+    for (index, time_section) in input.split(':').rev().enumerate() {
+        if index > 0 {
+            multiplier = multiplier.checked_mul(60)?;
         }
-        multiplier *= 60;
-    }
-    
-    Duration::from_secs(seconds)
-}
-
-pub async fn search(query: &str) -> (Option<String>, Option<String>, Option<Duration>) {
-    let formatted_query = query.replace(" ", "+");
-    let client = Client::new();
-    let response = client.get(format!("https://www.youtube.com/results?search_query={}", formatted_query)).send().await.unwrap().text().await.unwrap();
-    let doc = Html::parse_document(&response);
-    
-    let mut title: Option<String> = None;
-    let mut video_id: Option<String> = None;
-    let mut duration: Option<Duration> = None;
-    for i in doc.select(&Selector::parse("script").unwrap()) {
-        let mut html = i.inner_html();
-        if html.contains("var ytInitialData = ") { 
-            if let Ok((remainder, matched)) = parse_video_id(&html) {
-                video_id = Some(matched.to_owned());
-                html = remainder.to_owned();
-            }
-            if let Ok((_, matched)) = parse_title(&html) {
-                title = Some(matched.to_owned());
-            }
-            if let Ok((_, matched)) = parse_duration_text(&html) {
-                duration = Some(string_to_duration(matched));
-            }
-            break;
+        if let Ok(time_section) = time_section.trim().parse::<u64>() {
+            seconds = seconds.checked_add(time_section.checked_mul(multiplier)?)?;
         }
     }
-    (title, video_id, duration)
+
+    Some(Duration::from_secs(seconds))
+}
+
+/// The results page's videos in the order YouTube ranks them
+pub async fn search(query: &str) -> Result<Vec<SearchResult>, Error> {
+    let response = Client::new()
+        .get("https://www.youtube.com/results")
+        .query(&[("search_query", query)])
+        .send().await?
+        .text().await?;
+    parse_search_results(&response)
+}
+
+fn parse_search_results(html: &str) -> Result<Vec<SearchResult>, Error> {
+    let initial_data: InitialData = extract_json(html, "ytInitialData")?;
+    let sections = initial_data.contents
+        .and_then(|contents| contents.two_column_search_results_renderer)
+        .map(|renderer| renderer.primary_contents.section_list_renderer.contents)
+        .unwrap_or_default();
+
+    Ok(sections.into_iter()
+        .filter_map(|section| section.item_section_renderer)
+        .flat_map(|item_section| item_section.contents)
+        .filter_map(|item| item.video_renderer)
+        .map(SearchResult::from)
+        .collect())
+}
+
+/// Reads the object a page script assigns to `name`, ignoring the script that follows it
+fn extract_json<T: DeserializeOwned>(html: &str, name: &str) -> Result<T, Error> {
+    let assignments = [format!("var {} = ", name), format!("window[\"{}\"] = ", name), format!("{} = ", name)];
+    let start = assignments.iter()
+        .find_map(|assignment| html.find(assignment.as_str()).map(|start| start + assignment.len()))
+        .ok_or(missing_value!(name))?;
+    serde_json::Deserializer::from_str(&html[start..])
+        .into_iter::<T>()
+        .next()
+        .ok_or(missing_value!(name))?
+        .map_err(|err| err.into())
+}
+
+/// Reads a playlist's videos from its page, for when neither the Data API nor yt-dlp are available.
+/// Only the first hundred are on the page, the rest would need YouTube's continuation requests
+pub async fn playlist_metadata(id: &str) -> Result<Vec<Metadata>, Error> {
+    let response = Client::new()
+        .get("https://www.youtube.com/playlist")
+        .query(&[("list", id)])
+        .send().await?
+        .text().await?;
+    Ok(parse_playlist_videos(&response)?.iter().map(SearchResult::metadata).collect())
+}
+
+fn parse_playlist_videos(html: &str) -> Result<Vec<SearchResult>, Error> {
+    let initial_data: InitialData = extract_json(html, "ytInitialData")?;
+    let sections = initial_data.contents
+        .and_then(|contents| contents.two_column_browse_results_renderer)
+        .and_then(|renderer| renderer.tabs.into_iter().find_map(|tab| tab.tab_renderer?.content?.section_list_renderer))
+        .ok_or(missing_value!("playlistVideoListRenderer"))?
+        .contents;
+
+    Ok(sections.into_iter()
+        .filter_map(|section| section.item_section_renderer)
+        .flat_map(|item_section| item_section.contents)
+        .filter_map(|item| item.playlist_video_list_renderer)
+        .flat_map(|playlist| playlist.contents)
+        .filter_map(|item| item.playlist_video_renderer)
+        .filter(|video_renderer| video_renderer.is_playable != Some(false))
+        .map(SearchResult::from)
+        .collect())
+}
+
+/// Reads a video's title and length from its watch page, for when neither the Data API nor yt-dlp are available
+pub async fn video_metadata(id: &str) -> Result<Metadata, Error> {
+    let response = Client::new().get(format!("https://www.youtube.com/watch?v={}", id)).send().await?.text().await?;
+    parse_video_metadata(&response)
+}
+
+fn parse_video_metadata(html: &str) -> Result<Metadata, Error> {
+    let player_response: serde_json::Value = extract_json(html, "ytInitialPlayerResponse")?;
+    let video_details = player_response.get("videoDetails").ok_or(missing_value!("videoDetails"))?;
+    let video_id = video_details.get("videoId").and_then(serde_json::Value::as_str).ok_or(missing_value!("videoId"))?;
+    let is_live = video_details.get("isLive").and_then(serde_json::Value::as_bool).unwrap_or(false);
+
+    let duration = if is_live {
+        None
+    } else {
+        let length_seconds = video_details.get("lengthSeconds").and_then(serde_json::Value::as_str).and_then(|length| length.parse::<u64>().ok());
+        Some(Duration::from_secs(length_seconds.unwrap_or(0)))
+    };
+    Ok(Metadata {
+        title: video_details.get("title").and_then(serde_json::Value::as_str).map(|title| title.to_owned()),
+        channel: video_details.get("author").and_then(serde_json::Value::as_str).map(|author| author.to_owned()),
+        duration,
+        source_url: Some(format!("https://youtu.be/{}", video_id)),
+        channels: Some(2),
+        sample_rate: Some(48000),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_watch_page_metadata() {
+        let html = r#"<script>var ytInitialPlayerResponse = {"videoDetails":{"videoId":"dQw4w9WgXcQ","title":"Rick Astley - Never Gonna Give You Up (Official Music Video)","lengthSeconds":"212","author":"Rick Astley","isLive":false}};var meta = document.createElement('meta');</script>"#;
+        let metadata = parse_video_metadata(html).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Rick Astley - Never Gonna Give You Up (Official Music Video)"));
+        assert_eq!(metadata.duration, Some(Duration::from_secs(212)));
+        assert_eq!(metadata.source_url.as_deref(), Some("https://youtu.be/dQw4w9WgXcQ"));
+
+        let html = r#"ytInitialPlayerResponse = {"videoDetails":{"videoId":"jfKfPfyJRdk","title":"lofi hip hop radio","lengthSeconds":"0","isLive":true}};"#;
+        assert_eq!(parse_video_metadata(html).unwrap().duration, None);
+
+        assert!(parse_video_metadata("<html></html>").is_err());
+    }
+
+    #[test]
+    fn reads_search_results_together() {
+        let results = parse_search_results(include_str!("../tests/fixtures/youtube_search.html")).unwrap();
+        assert_eq!(results.iter().map(|result| result.video_id.as_str()).collect::<Vec<&str>>(), vec!["dQw4w9WgXcQ", "ZtvsH6nZk5U", "jfKfPfyJRdk"]);
+
+        assert_eq!(results[0], SearchResult {
+            video_id: "dQw4w9WgXcQ".to_owned(),
+            title: "Rick Astley - Never Gonna Give You Up (Official Music Video)".to_owned(),
+            channel: Some("Rick Astley".to_owned()),
+            duration: Some(Duration::from_secs(213))
+        });
+        assert_eq!(results[0].url(), "https://youtu.be/dQw4w9WgXcQ");
+        assert_eq!(results[1].title, "Never Gonna Give You Up \"Live\" – Rick Astley • Café Sessions");
+        assert_eq!(results[1].duration, Some(Duration::from_secs(3725)));
+        assert_eq!(results[2].duration, None);
+    }
+
+    #[test]
+    fn reads_lengths() {
+        assert_eq!(string_to_duration("3:33"), Some(Duration::from_secs(213)));
+        assert_eq!(string_to_duration("1:02:05"), Some(Duration::from_secs(3725)));
+        assert_eq!(string_to_duration("99999999999999999:00:00"), None);
+        assert_eq!(string_to_duration(&["1"; 20].join(":")), None);
+    }
+
+    #[test]
+    fn reads_playlist_pages() {
+        let results = parse_playlist_videos(include_str!("../tests/fixtures/youtube_playlist.html")).unwrap();
+        assert_eq!(results.iter().map(|result| result.video_id.as_str()).collect::<Vec<&str>>(), vec!["dQw4w9WgXcQ", "yPYZpwSpKmA", "jfKfPfyJRdk"]);
+        assert_eq!(results[0], SearchResult {
+            video_id: "dQw4w9WgXcQ".to_owned(),
+            title: "Rick Astley - Never Gonna Give You Up (Official Music Video)".to_owned(),
+            channel: Some("Rick Astley".to_owned()),
+            duration: Some(Duration::from_secs(213))
+        });
+        assert_eq!(results[2].duration, None);
+
+        assert!(parse_playlist_videos(include_str!("../tests/fixtures/youtube_search.html")).is_err());
+    }
+
+    #[test]
+    fn reads_pages_without_results() {
+        assert_eq!(parse_search_results(include_str!("../tests/fixtures/youtube_search_empty.html")).unwrap(), vec![]);
+        assert!(parse_search_results("<html><script>var ytInitialData = {\"contents\":</script></html>").is_err());
+        assert!(parse_search_results("<html></html>").is_err());
+    }
 }
\ No newline at end of file